@@ -0,0 +1,397 @@
+use rayon::prelude::*;
+
+use super::empirical::{EmpiricalModel, Matrix};
+use super::rates::RateVariation;
+use super::{nucleotide, protein, Alignment};
+use crate::{DistanceMatrix, Error, Result};
+
+/// Evolutionary model used to correct the observed differences between two sequences
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DistanceModel {
+    /// Proportion of differing sites, without correction
+    PDistance,
+    /// Jukes and Cantor (1969)
+    JukesCantor,
+    /// Kimura (1980) two-parameter model
+    Kimura2P,
+    /// Tamura and Nei (1993)
+    TamuraNei,
+    /// Felsenstein's F84, as in PHYLIP `dnadist`
+    F84,
+    /// LogDet / paralinear distance (Lake 1994)
+    LogDet,
+    /// Poisson correction for proteins
+    Poisson,
+    /// Kimura (1983) approximation for proteins, as in QuickTree and RapidNJ
+    KimuraProtein,
+    /// Maximum likelihood under the JTT matrix (Jones et al. 1992)
+    Jtt,
+    /// Maximum likelihood under the WAG matrix (Whelan and Goldman 2001)
+    Wag,
+    /// Maximum likelihood under the LG matrix (Le and Gascuel 2008)
+    Lg,
+}
+
+/// Kind of sequences of an alignment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Alphabet {
+    Nucleotide,
+    Protein,
+}
+
+impl DistanceModel {
+    /// Alphabet the model applies to, None if it applies to both
+    fn alphabet(&self) -> Option<Alphabet> {
+        match self {
+            DistanceModel::PDistance => None,
+            DistanceModel::JukesCantor
+            | DistanceModel::Kimura2P
+            | DistanceModel::TamuraNei
+            | DistanceModel::F84
+            | DistanceModel::LogDet => Some(Alphabet::Nucleotide),
+            DistanceModel::Poisson
+            | DistanceModel::KimuraProtein
+            | DistanceModel::Jtt
+            | DistanceModel::Wag
+            | DistanceModel::Lg => Some(Alphabet::Protein),
+        }
+    }
+    /// Whether the model corrects for rate variation across sites
+    fn supports_rate_variation(&self) -> bool {
+        !matches!(self, DistanceModel::PDistance | DistanceModel::LogDet)
+    }
+    /// Empirical matrix of the maximum likelihood models
+    fn matrix(&self) -> Option<Matrix> {
+        match self {
+            DistanceModel::Jtt => Some(Matrix::Jtt),
+            DistanceModel::Wag => Some(Matrix::Wag),
+            DistanceModel::Lg => Some(Matrix::Lg),
+            _ => None,
+        }
+    }
+}
+
+/// Computes a [`DistanceMatrix`] from an [`Alignment`] under a [`DistanceModel`].
+/// Gaps and ambiguity codes are handled with pairwise deletion.
+/// Rows of the matrix are computed in parallel with the available rayon threads,
+/// and nucleotides are compared as packed bit-vectors.
+#[derive(Debug, Clone)]
+pub struct DistanceCalculator {
+    model: DistanceModel,
+    max_distance: f64,
+    rates: RateVariation,
+}
+
+impl DistanceCalculator {
+    /// Construct calculator from parameters
+    pub fn build(model: DistanceModel, max_distance: f64) -> Result<Self> {
+        check_max_distance(max_distance)?;
+        Ok(DistanceCalculator {
+            model,
+            max_distance,
+            rates: RateVariation::default(),
+        })
+    }
+    /// Default calculator (saturated pairs get a distance of 10)
+    pub fn default(model: DistanceModel) -> Self {
+        DistanceCalculator {
+            model,
+            max_distance: 10.0,
+            rates: RateVariation::default(),
+        }
+    }
+    /// Set the distance given to saturated pairs, where the correction is undefined.
+    /// Larger corrected distances are capped to this value too.
+    pub fn set_max_distance(self, max_distance: f64) -> Result<Self> {
+        check_max_distance(max_distance)?;
+        Ok(DistanceCalculator {
+            max_distance,
+            ..self
+        })
+    }
+    /// Set the shape (alpha) of the gamma distribution of rates across sites.
+    /// Smaller values mean stronger rate variation.
+    pub fn set_gamma(self, alpha: f64) -> Result<Self> {
+        if !(alpha.is_finite() && alpha > 0.0) {
+            return Err(Error::InvalidParameter(
+                "gamma shape must be positive and finite".to_string(),
+            ));
+        }
+        let rates = RateVariation {
+            alpha: Some(alpha),
+            ..self.rates
+        };
+        Ok(DistanceCalculator { rates, ..self })
+    }
+    /// Set the proportion of invariant sites
+    pub fn set_invariant_sites(self, pinv: f64) -> Result<Self> {
+        if !(0.0..1.0).contains(&pinv) {
+            return Err(Error::InvalidParameter(
+                "proportion of invariant sites must be in [0, 1)".to_string(),
+            ));
+        }
+        let rates = RateVariation { pinv, ..self.rates };
+        Ok(DistanceCalculator { rates, ..self })
+    }
+    /// Compute the distance between every pair of sequences
+    pub fn compute(&self, alignment: &Alignment) -> Result<DistanceMatrix> {
+        let alphabet = if alignment.is_nucleotide() {
+            Alphabet::Nucleotide
+        } else {
+            Alphabet::Protein
+        };
+        match self.model.alphabet() {
+            Some(Alphabet::Nucleotide) if alphabet == Alphabet::Protein => {
+                return Err(Error::InvalidParameter(format!(
+                    "{:?} requires a nucleotide alignment",
+                    self.model
+                )));
+            }
+            Some(Alphabet::Protein) if alphabet == Alphabet::Nucleotide => {
+                return Err(Error::InvalidParameter(format!(
+                    "{:?} requires a protein alignment",
+                    self.model
+                )));
+            }
+            _ => {}
+        }
+        if !self.rates.is_uniform() && !self.model.supports_rate_variation() {
+            return Err(Error::InvalidParameter(format!(
+                "{:?} does not support rate variation across sites",
+                self.model
+            )));
+        }
+        let rates = &self.rates;
+        let empirical = self.model.matrix().map(EmpiricalModel::new);
+        let freqs = nucleotide::base_frequencies(alignment);
+        if matches!(self.model, DistanceModel::TamuraNei | DistanceModel::F84)
+            && freqs.contains(&0.0)
+        {
+            return Err(Error::Numerical(format!(
+                "{:?} requires the four nucleotides in the alignment",
+                self.model
+            )));
+        }
+        let packed: Vec<nucleotide::PackedSequence> = match alphabet {
+            Alphabet::Nucleotide => alignment
+                .sequences
+                .par_iter()
+                .map(|seq| nucleotide::PackedSequence::pack(seq))
+                .collect(),
+            Alphabet::Protein => Vec::new(),
+        };
+        let pair_distance = |i: usize, j: usize| -> f64 {
+            let distance = match alphabet {
+                Alphabet::Nucleotide => {
+                    let counts = nucleotide::PairCounts::count_packed(&packed[i], &packed[j]);
+                    match self.model {
+                        DistanceModel::JukesCantor => nucleotide::jukes_cantor(&counts, rates),
+                        DistanceModel::Kimura2P => nucleotide::kimura(&counts, rates),
+                        DistanceModel::TamuraNei => nucleotide::tamura_nei(&counts, &freqs, rates),
+                        DistanceModel::F84 => nucleotide::f84(&counts, &freqs, rates),
+                        DistanceModel::LogDet => nucleotide::logdet(&counts),
+                        _ => nucleotide::p_distance(&counts),
+                    }
+                }
+                Alphabet::Protein => {
+                    let counts = protein::ProteinCounts::count(
+                        &alignment.sequences[i],
+                        &alignment.sequences[j],
+                    );
+                    match (self.model, &empirical) {
+                        (DistanceModel::Poisson, _) => protein::poisson(&counts, rates),
+                        (DistanceModel::KimuraProtein, _) => protein::kimura(&counts, rates),
+                        (_, Some(empirical)) => {
+                            empirical.distance(&counts, self.max_distance, rates)
+                        }
+                        _ => protein::p_distance(&counts),
+                    }
+                }
+            };
+            distance
+                .filter(|d| d.is_finite())
+                .map_or(self.max_distance, |d| d.min(self.max_distance))
+        };
+        // Every thread computes whole rows of the lower triangle, collected in order
+        let n = alignment.len();
+        let lower: Vec<f64> = (0..n)
+            .into_par_iter()
+            .flat_map_iter(|i| (0..i).map(move |j| pair_distance(i, j)))
+            .collect();
+        DistanceMatrix::from_lower_triangle(lower, alignment.names.clone())
+    }
+}
+
+fn check_max_distance(max_distance: f64) -> Result<()> {
+    if !(max_distance.is_finite() && max_distance > 0.0) {
+        return Err(Error::InvalidParameter(
+            "maximum distance must be positive and finite".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alignment(sequences: &[&str]) -> Alignment {
+        Alignment::build(
+            (0..sequences.len()).map(|i| format!("s{i}")).collect(),
+            sequences
+                .iter()
+                .map(|seq| seq.as_bytes().to_vec())
+                .collect(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_saturated_pairs_are_capped() {
+        let aln = alignment(&["ACGTACGT", "CATGCATG", "ACGTACGA"]);
+        let d = DistanceCalculator::default(DistanceModel::JukesCantor)
+            .set_max_distance(3.0)
+            .unwrap()
+            .compute(&aln)
+            .unwrap();
+        assert_eq!(d.get(0, 1), 3.0);
+        assert!(d.get(0, 2) < 3.0);
+        assert!(d.validate().is_valid());
+    }
+
+    #[test]
+    fn test_nucleotide_models_require_nucleotides() {
+        let aln = alignment(&["MDPELAKR", "MDPQLAKR", "MEPELAKR"]);
+        assert!(matches!(
+            DistanceCalculator::default(DistanceModel::Kimura2P).compute(&aln),
+            Err(Error::InvalidParameter(_))
+        ));
+        let d = DistanceCalculator::default(DistanceModel::PDistance)
+            .compute(&aln)
+            .unwrap();
+        assert_eq!(d.get(0, 1), 1.0 / 8.0);
+    }
+
+    #[test]
+    fn test_protein_models() {
+        let aln = alignment(&["MDPELAKRWVGHSTNQ", "MEPELAKRWYGHSTNQ", "MDPQLAKRWVGHSTXX"]);
+        assert!(DistanceCalculator::default(DistanceModel::Jtt)
+            .compute(&alignment(&["ACGT", "ACGA", "ACTT"]))
+            .is_err());
+        let p = DistanceCalculator::default(DistanceModel::PDistance)
+            .compute(&aln)
+            .unwrap();
+        // Ambiguous X are ignored
+        assert_eq!(p.get(0, 2), 1.0 / 14.0);
+        for model in [
+            DistanceModel::Poisson,
+            DistanceModel::KimuraProtein,
+            DistanceModel::Jtt,
+            DistanceModel::Wag,
+            DistanceModel::Lg,
+        ] {
+            let d = DistanceCalculator::default(model).compute(&aln).unwrap();
+            assert!(d.validate().is_valid());
+            assert!(d.get(0, 1) > p.get(0, 1));
+            assert!(d.get(0, 1) > d.get(0, 2));
+        }
+    }
+
+    #[test]
+    fn test_rate_variation() {
+        let aln = alignment(&[
+            "AAAAACCCCCGGGGGTTTTT",
+            "GAAAATCCCCGGGGGTTTTA",
+            "GAAAATCCCCAGGCGTTTTA",
+        ]);
+        for model in [
+            DistanceModel::JukesCantor,
+            DistanceModel::Kimura2P,
+            DistanceModel::TamuraNei,
+            DistanceModel::F84,
+        ] {
+            let uniform = DistanceCalculator::default(model).compute(&aln).unwrap();
+            let gamma = DistanceCalculator::default(model)
+                .set_gamma(0.5)
+                .unwrap()
+                .compute(&aln)
+                .unwrap();
+            let invariant = DistanceCalculator::default(model)
+                .set_gamma(0.5)
+                .unwrap()
+                .set_invariant_sites(0.2)
+                .unwrap()
+                .compute(&aln)
+                .unwrap();
+            assert!(gamma.get(0, 2) > uniform.get(0, 2));
+            assert!(invariant.get(0, 2) > gamma.get(0, 2));
+        }
+        assert!(DistanceCalculator::default(DistanceModel::LogDet)
+            .set_gamma(0.5)
+            .unwrap()
+            .compute(&aln)
+            .is_err());
+        let aln = alignment(&["MDPELAKRWVGHSTNQ", "MEPELAKRWYGHSTNQ", "MDPQLAKRWVGHSTCC"]);
+        for model in [
+            DistanceModel::Poisson,
+            DistanceModel::KimuraProtein,
+            DistanceModel::Lg,
+        ] {
+            let uniform = DistanceCalculator::default(model).compute(&aln).unwrap();
+            let gamma = DistanceCalculator::default(model)
+                .set_gamma(0.5)
+                .unwrap()
+                .compute(&aln)
+                .unwrap();
+            assert!(gamma.get(0, 2) > uniform.get(0, 2));
+        }
+    }
+
+    #[test]
+    fn test_parallel_rows() {
+        use rand::{Rng, SeedableRng};
+        let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+        let sequences: Vec<String> = (0..20)
+            .map(|_| {
+                (0..150)
+                    .map(|_| b"ACGTACGTN-"[rng.gen_range(0..10)] as char)
+                    .collect()
+            })
+            .collect();
+        let aln = alignment(&sequences.iter().map(|seq| seq.as_str()).collect::<Vec<_>>());
+        let compute = |threads: usize| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap()
+                .install(|| {
+                    DistanceCalculator::default(DistanceModel::Kimura2P)
+                        .compute(&aln)
+                        .unwrap()
+                })
+        };
+        let d = compute(1);
+        assert_eq!(d.to_rows(), compute(4).to_rows());
+        assert!(d.validate().is_valid());
+    }
+
+    #[test]
+    fn test_invalid_max_distance() {
+        for max_distance in [f64::NAN, -1.0] {
+            assert!(matches!(
+                DistanceCalculator::build(DistanceModel::F84, max_distance),
+                Err(Error::InvalidParameter(_))
+            ));
+        }
+        assert!(DistanceCalculator::build(DistanceModel::F84, 3.0).is_ok());
+        assert!(DistanceCalculator::default(DistanceModel::F84)
+            .set_max_distance(f64::NAN)
+            .is_err());
+        assert!(DistanceCalculator::default(DistanceModel::F84)
+            .set_gamma(0.0)
+            .is_err());
+        assert!(DistanceCalculator::default(DistanceModel::F84)
+            .set_invariant_sites(1.0)
+            .is_err());
+    }
+}
@@ -0,0 +1,89 @@
+use std::io;
+
+use super::Alignment;
+use crate::{Error, Result};
+
+impl Alignment {
+    /// Read an aligned [FASTA](https://en.wikipedia.org/wiki/FASTA_format) file.
+    /// The name of every sequence is the first word of its header.
+    pub fn read_fasta<R>(reader: R) -> Result<Alignment>
+    where
+        R: io::BufRead,
+    {
+        let mut names = Vec::new();
+        let mut sequences: Vec<Vec<u8>> = Vec::new();
+        let mut header_lines = Vec::new();
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line_number = index + 1;
+            if let Some(header) = line.strip_prefix('>') {
+                let name = header
+                    .split_whitespace()
+                    .next()
+                    .ok_or_else(|| Error::parse(line_number, "missing sequence name"))?;
+                names.push(name.to_string());
+                sequences.push(Vec::new());
+                header_lines.push(line_number);
+            } else if !line.trim().is_empty() {
+                let sequence = sequences.last_mut().ok_or_else(|| {
+                    Error::parse(line_number, "sequence found before the first header")
+                })?;
+                sequence.extend(line.bytes().filter(|c| !c.is_ascii_whitespace()));
+            }
+        }
+        if let Some(first) = sequences.first() {
+            for (i, sequence) in sequences.iter().enumerate() {
+                if sequence.len() != first.len() {
+                    return Err(Error::parse(
+                        header_lines[i],
+                        format!(
+                            "sequence '{}' has {} sites, expected {}",
+                            names[i],
+                            sequence.len(),
+                            first.len()
+                        ),
+                    ));
+                }
+            }
+        }
+        Alignment::build(names, sequences)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_fasta() {
+        let input = ">a first sequence
+ACGT
+AC
+>b
+ACGTTT
+
+>c
+acg-tt
+"
+        .as_bytes();
+        let aln = Alignment::read_fasta(input).unwrap();
+        assert_eq!(aln.names, vec!["a", "b", "c"]);
+        assert_eq!(aln.sequences[0], b"ACGTAC");
+        assert_eq!(aln.sequences[2], b"ACG-TT");
+    }
+
+    #[test]
+    fn test_unaligned_fasta() {
+        let input = ">a
+ACGT
+>b
+ACG
+"
+        .as_bytes();
+        let err = Alignment::read_fasta(input).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 3: sequence 'b' has 3 sites, expected 4"
+        );
+    }
+}
@@ -0,0 +1,131 @@
+// Distance models and the calculator of distance matrices
+mod calculator;
+// Empirical amino acid models and their maximum likelihood distances
+mod empirical;
+// Reader of aligned FASTA files
+mod fasta;
+// Counts and distance models of nucleotide alignments
+mod nucleotide;
+// Reader of sequential and interleaved PHYLIP alignments
+mod phylip;
+// Counts and distance corrections of protein alignments
+mod protein;
+// Rate variation across sites
+mod rates;
+// Reader of Stockholm files
+mod stockholm;
+
+pub use calculator::{DistanceCalculator, DistanceModel};
+
+use crate::{DistanceMatrix, Error, Result};
+
+/// Multiple sequence alignment. Every sequence is stored in upper case.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alignment {
+    /// Names of the sequences
+    pub names: Vec<String>,
+    /// Aligned sequences, all of them with the same length
+    pub sequences: Vec<Vec<u8>>,
+}
+
+impl Alignment {
+    /// Build an alignment, checking that every sequence has a name and the same length
+    pub fn build(names: Vec<String>, sequences: Vec<Vec<u8>>) -> Result<Alignment> {
+        if names.len() != sequences.len() {
+            return Err(Error::InvalidParameter(format!(
+                "{} names for {} sequences",
+                names.len(),
+                sequences.len()
+            )));
+        }
+        if let Some(first) = sequences.first() {
+            if let Some(i) = sequences.iter().position(|seq| seq.len() != first.len()) {
+                return Err(Error::InvalidParameter(format!(
+                    "sequence '{}' has {} sites, expected {}",
+                    names[i],
+                    sequences[i].len(),
+                    first.len()
+                )));
+            }
+        }
+        let sequences = sequences
+            .into_iter()
+            .map(|seq| seq.to_ascii_uppercase())
+            .collect();
+        Ok(Alignment { names, sequences })
+    }
+    /// Number of sequences
+    pub fn len(&self) -> usize {
+        self.sequences.len()
+    }
+    /// Whether the alignment has no sequences
+    pub fn is_empty(&self) -> bool {
+        self.sequences.is_empty()
+    }
+    /// Number of columns of the alignment
+    pub fn n_sites(&self) -> usize {
+        self.sequences.first().map_or(0, |seq| seq.len())
+    }
+    /// Whether every character is a nucleotide, an ambiguity code or a gap
+    pub fn is_nucleotide(&self) -> bool {
+        self.sequences
+            .iter()
+            .all(|seq| seq.iter().all(|c| nucleotide::is_nucleotide(*c)))
+    }
+    /// Proportion of differing sites between every pair of sequences (p-distance).
+    /// Sites with a gap or unknown character in either sequence are ignored (pairwise deletion).
+    /// Pairs without any comparable site get a distance of 1.
+    pub fn distance_matrix(&self) -> Result<DistanceMatrix> {
+        DistanceCalculator::default(DistanceModel::PDistance).compute(self)
+    }
+}
+
+/// Gap and unknown characters
+fn is_missing(c: u8) -> bool {
+    matches!(c, b'-' | b'.' | b'?' | b'~')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_checks_lengths() {
+        let names = vec!["a".to_string(), "b".to_string()];
+        let err = Alignment::build(names.clone(), vec![b"ACGT".to_vec(), b"AC".to_vec()]);
+        assert_eq!(
+            err.unwrap_err().to_string(),
+            "invalid parameter: sequence 'b' has 2 sites, expected 4"
+        );
+        let err = Alignment::build(names.clone(), vec![b"ACGT".to_vec()]);
+        assert_eq!(
+            err.unwrap_err().to_string(),
+            "invalid parameter: 2 names for 1 sequences"
+        );
+        let aln = Alignment::build(names, vec![b"acgt".to_vec(), b"ACGA".to_vec()]).unwrap();
+        assert_eq!(aln.sequences[0], b"ACGT");
+        assert_eq!(aln.n_sites(), 4);
+    }
+
+    #[test]
+    fn test_p_distance_with_gaps() {
+        let names = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let aln = Alignment::build(
+            names,
+            vec![b"ACGT-A".to_vec(), b"ACGAAA".to_vec(), b"TCGA.A".to_vec()],
+        )
+        .unwrap();
+        let d = aln.distance_matrix().unwrap();
+        assert_eq!(d.get(0, 1), 1.0 / 5.0);
+        assert_eq!(d.get(0, 2), 2.0 / 5.0);
+        assert_eq!(d.get(1, 2), 1.0 / 5.0);
+        assert_eq!(d.get(2, 1), 1.0 / 5.0);
+    }
+
+    #[test]
+    fn test_no_comparable_sites() {
+        let names = vec!["a".to_string(), "b".to_string()];
+        let aln = Alignment::build(names, vec![b"AC--".to_vec(), b"--GT".to_vec()]).unwrap();
+        assert_eq!(aln.distance_matrix().unwrap().get(0, 1), 1.0);
+    }
+}
@@ -0,0 +1,324 @@
+use super::rates::RateVariation;
+use super::Alignment;
+
+/// Index of an unambiguous nucleotide (A, C, G, T/U). Gaps and ambiguity codes are None.
+pub(crate) fn encode(c: u8) -> Option<usize> {
+    match c {
+        b'A' => Some(0),
+        b'C' => Some(1),
+        b'G' => Some(2),
+        b'T' | b'U' => Some(3),
+        _ => None,
+    }
+}
+
+/// Whether the character is a nucleotide, an IUPAC ambiguity code or a gap
+pub(crate) fn is_nucleotide(c: u8) -> bool {
+    matches!(
+        c,
+        b'A' | b'C'
+            | b'G'
+            | b'T'
+            | b'U'
+            | b'R'
+            | b'Y'
+            | b'K'
+            | b'M'
+            | b'S'
+            | b'W'
+            | b'B'
+            | b'D'
+            | b'H'
+            | b'V'
+            | b'N'
+    ) || super::is_missing(c)
+}
+
+/// Number of sites with nucleotide x in the first sequence and y in the second one.
+/// Only sites with unambiguous nucleotides in both sequences are counted (pairwise deletion).
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct PairCounts {
+    pub counts: [[u32; 4]; 4],
+}
+
+impl PairCounts {
+    /// Reference implementation, site by site
+    #[cfg(test)]
+    pub fn count(a: &[u8], b: &[u8]) -> Self {
+        let mut counts = [[0; 4]; 4];
+        for (x, y) in a.iter().zip(b.iter()) {
+            if let (Some(x), Some(y)) = (encode(*x), encode(*y)) {
+                counts[x][y] += 1;
+            }
+        }
+        PairCounts { counts }
+    }
+    /// Same counts from packed sequences, with one popcount per pair of nucleotides and word
+    pub fn count_packed(a: &PackedSequence, b: &PackedSequence) -> Self {
+        let mut counts = [[0; 4]; 4];
+        for (x, row) in counts.iter_mut().enumerate() {
+            for (y, count) in row.iter_mut().enumerate() {
+                *count = a.planes[x]
+                    .iter()
+                    .zip(b.planes[y].iter())
+                    .map(|(u, v)| (u & v).count_ones())
+                    .sum();
+            }
+        }
+        PairCounts { counts }
+    }
+    pub fn sites(&self) -> u32 {
+        self.counts.iter().flatten().sum()
+    }
+    /// Proportion of A <-> G transitions
+    fn purine_transitions(&self) -> f64 {
+        (self.counts[0][2] + self.counts[2][0]) as f64 / self.sites() as f64
+    }
+    /// Proportion of C <-> T transitions
+    fn pyrimidine_transitions(&self) -> f64 {
+        (self.counts[1][3] + self.counts[3][1]) as f64 / self.sites() as f64
+    }
+    /// Proportion of differing sites
+    fn differences(&self) -> f64 {
+        let same: u32 = (0..4).map(|x| self.counts[x][x]).sum();
+        (self.sites() - same) as f64 / self.sites() as f64
+    }
+    /// Proportion of transversions
+    fn transversions(&self) -> f64 {
+        self.differences() - self.purine_transitions() - self.pyrimidine_transitions()
+    }
+}
+
+/// Sequence encoded as four bit-vectors, one per nucleotide, with a bit set at every site with that nucleotide.
+/// Gaps and ambiguity codes have no bit set in any of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PackedSequence {
+    planes: [Vec<u64>; 4],
+}
+
+impl PackedSequence {
+    pub fn pack(seq: &[u8]) -> Self {
+        let words = seq.len().div_ceil(64);
+        let mut planes: [Vec<u64>; 4] = std::array::from_fn(|_| vec![0; words]);
+        for (site, c) in seq.iter().enumerate() {
+            if let Some(x) = encode(*c) {
+                planes[x][site / 64] |= 1 << (site % 64);
+            }
+        }
+        PackedSequence { planes }
+    }
+}
+
+/// Frequencies of A, C, G and T in the whole alignment
+pub(crate) fn base_frequencies(alignment: &Alignment) -> [f64; 4] {
+    let mut counts = [0usize; 4];
+    for c in alignment.sequences.iter().flatten() {
+        if let Some(x) = encode(*c) {
+            counts[x] += 1;
+        }
+    }
+    let total = counts.iter().sum::<usize>().max(1) as f64;
+    counts.map(|count| count as f64 / total)
+}
+
+/// -ln(x), undefined when x is not positive
+fn neg_log(x: f64) -> Option<f64> {
+    if x > 0.0 {
+        Some(-x.ln())
+    } else {
+        None
+    }
+}
+
+pub(crate) fn p_distance(c: &PairCounts) -> Option<f64> {
+    if c.sites() == 0 {
+        return Some(1.0);
+    }
+    Some(c.differences())
+}
+
+/// Jukes and Cantor (1969)
+pub(crate) fn jukes_cantor(c: &PairCounts, rates: &RateVariation) -> Option<f64> {
+    if c.sites() == 0 {
+        return None;
+    }
+    Some(0.75 * rates.neg_log(1.0 - 4.0 / 3.0 * c.differences())?)
+}
+
+/// Kimura (1980) two-parameter model
+pub(crate) fn kimura(c: &PairCounts, rates: &RateVariation) -> Option<f64> {
+    if c.sites() == 0 {
+        return None;
+    }
+    let p = c.purine_transitions() + c.pyrimidine_transitions();
+    let q = c.transversions();
+    Some(0.5 * rates.neg_log(1.0 - 2.0 * p - q)? + 0.25 * rates.neg_log(1.0 - 2.0 * q)?)
+}
+
+/// Tamura and Nei (1993), with the base frequencies of the whole alignment
+pub(crate) fn tamura_nei(c: &PairCounts, freqs: &[f64; 4], rates: &RateVariation) -> Option<f64> {
+    if c.sites() == 0 {
+        return None;
+    }
+    let [a, cy, g, t] = *freqs;
+    let (r, y) = (a + g, cy + t);
+    let (p1, p2, q) = (
+        c.purine_transitions(),
+        c.pyrimidine_transitions(),
+        c.transversions(),
+    );
+    let ag = 2.0 * a * g / r;
+    let ct = 2.0 * cy * t / y;
+    Some(
+        ag * rates.neg_log(1.0 - p1 / ag - q / (2.0 * r))?
+            + ct * rates.neg_log(1.0 - p2 / ct - q / (2.0 * y))?
+            + 2.0
+                * (r * y - a * g * y / r - cy * t * r / y)
+                * rates.neg_log(1.0 - q / (2.0 * r * y))?,
+    )
+}
+
+/// Felsenstein's F84 model (as in PHYLIP `dnadist`), with the base frequencies of the whole alignment
+pub(crate) fn f84(c: &PairCounts, freqs: &[f64; 4], rates: &RateVariation) -> Option<f64> {
+    if c.sites() == 0 {
+        return None;
+    }
+    let [a, cy, g, t] = *freqs;
+    let (r, y) = (a + g, cy + t);
+    let big_a = cy * t / y + a * g / r;
+    let big_b = cy * t + a * g;
+    let big_c = r * y;
+    let p = c.purine_transitions() + c.pyrimidine_transitions();
+    let q = c.transversions();
+    Some(
+        2.0 * big_a
+            * rates
+                .neg_log(1.0 - p / (2.0 * big_a) - (big_a - big_b) * q / (2.0 * big_a * big_c))?
+            - 2.0 * (big_a - big_b - big_c) * rates.neg_log(1.0 - q / (2.0 * big_c))?,
+    )
+}
+
+/// LogDet / paralinear distance (Lake 1994). It does not assume stationary base frequencies.
+pub(crate) fn logdet(c: &PairCounts) -> Option<f64> {
+    let sites = c.sites();
+    if sites == 0 {
+        return None;
+    }
+    let mut f = [[0.0; 4]; 4];
+    for (x, row) in c.counts.iter().enumerate() {
+        for (y, count) in row.iter().enumerate() {
+            f[x][y] = *count as f64 / sites as f64;
+        }
+    }
+    let fx: f64 = f.iter().map(|row| row.iter().sum::<f64>()).product();
+    let fy: f64 = (0..4)
+        .map(|y| f.iter().map(|row| row[y]).sum::<f64>())
+        .product();
+    let det = determinant(f);
+    // Missing nucleotides make the divergence matrix singular
+    if fx <= 0.0 || fy <= 0.0 {
+        return None;
+    }
+    Some(0.25 * (neg_log(det)? + 0.5 * (fx.ln() + fy.ln())))
+}
+
+/// Determinant of a 4x4 matrix with Gaussian elimination and partial pivoting
+fn determinant(mut m: [[f64; 4]; 4]) -> f64 {
+    let mut det = 1.0;
+    for col in 0..4 {
+        let pivot = (col..4)
+            .max_by(|a, b| m[*a][col].abs().total_cmp(&m[*b][col].abs()))
+            .expect("Non-empty range");
+        if m[pivot][col] == 0.0 {
+            return 0.0;
+        }
+        if pivot != col {
+            m.swap(pivot, col);
+            det = -det;
+        }
+        det *= m[col][col];
+        let pivot_row = m[col];
+        for row in m.iter_mut().skip(col + 1) {
+            let factor = row[col] / pivot_row[col];
+            for (x, p) in row.iter_mut().zip(pivot_row.iter()).skip(col) {
+                *x -= factor * p;
+            }
+        }
+    }
+    det
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counts(a: &str, b: &str) -> PairCounts {
+        PairCounts::count(a.as_bytes(), b.as_bytes())
+    }
+
+    #[test]
+    fn test_counts_ignore_ambiguity_codes() {
+        let c = counts("ACGTNRA-", "GCGTAAAA");
+        assert_eq!(c.sites(), 5);
+        assert_eq!(c.counts[0][2], 1);
+        assert_eq!(c.counts[0][0], 1);
+    }
+
+    #[test]
+    fn test_packed_counts() {
+        use rand::{Rng, SeedableRng};
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        let alphabet = b"ACGTACGTACGTNRY-";
+        for len in [0, 1, 63, 64, 65, 200] {
+            let mut random = || -> Vec<u8> {
+                (0..len)
+                    .map(|_| alphabet[rng.gen_range(0..alphabet.len())])
+                    .collect()
+            };
+            let (a, b) = (random(), random());
+            assert_eq!(
+                PairCounts::count_packed(&PackedSequence::pack(&a), &PackedSequence::pack(&b)),
+                PairCounts::count(&a, &b)
+            );
+        }
+    }
+
+    #[test]
+    fn test_models_agree_on_equal_frequencies() {
+        let rates = RateVariation::default();
+        // 20 sites, a purine and a pyrimidine transition and 1 transversion
+        let a = "AAAAACCCCCGGGGGTTTTT";
+        let b = "GAAAATCCCCGGGGGTTTTA";
+        let c = counts(a, b);
+        let p: f64 = 3.0 / 20.0;
+        let jc = jukes_cantor(&c, &rates).unwrap();
+        assert!((jc - (-0.75 * (1.0 - 4.0 / 3.0 * p).ln())).abs() < 1e-12);
+        let k2p = kimura(&c, &rates).unwrap();
+        let expected = -0.5 * (1.0 - 2.0 * 0.1 - 0.05f64).ln() - 0.25 * (1.0 - 2.0 * 0.05f64).ln();
+        assert!((k2p - expected).abs() < 1e-12);
+        // With equal base frequencies both TN93 and F84 reduce to K2P
+        let freqs = [0.25; 4];
+        assert!((tamura_nei(&c, &freqs, &rates).unwrap() - k2p).abs() < 1e-12);
+        assert!((f84(&c, &freqs, &rates).unwrap() - k2p).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_logdet() {
+        let same = counts("ACGTACGT", "ACGTACGT");
+        assert!(logdet(&same).unwrap().abs() < 1e-12);
+        // A base missing in one sequence makes the distance undefined
+        assert_eq!(logdet(&counts("AAGT", "ACGT")), None);
+        let c = counts("AAAAACCCCCGGGGGTTTTT", "GAAAACCCCCAGGGGTTTTA");
+        assert!(logdet(&c).unwrap() > 0.0);
+    }
+
+    #[test]
+    fn test_saturation() {
+        let rates = RateVariation::default();
+        let c = counts("ACGTACGT", "CATGCATG");
+        assert_eq!(p_distance(&c), Some(1.0));
+        assert_eq!(jukes_cantor(&c, &rates), None);
+        assert_eq!(kimura(&c, &rates), None);
+        assert_eq!(jukes_cantor(&counts("--", "AC"), &rates), None);
+    }
+}
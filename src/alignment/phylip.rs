@@ -0,0 +1,196 @@
+use std::io;
+
+use super::Alignment;
+use crate::{Error, PhylipNames, Result};
+
+/// Non-empty lines of the file (after the header) and their line numbers
+type Lines = [(usize, String)];
+
+fn residues(text: &str) -> impl Iterator<Item = u8> + '_ {
+    text.bytes().filter(|c| !c.is_ascii_whitespace())
+}
+
+/// Every taxon in one or more consecutive lines
+fn read_sequential(
+    lines: &Lines,
+    n: usize,
+    m: usize,
+    names_format: PhylipNames,
+) -> Result<Alignment> {
+    let mut lines = lines.iter();
+    let mut last = 1;
+    let mut names = Vec::with_capacity(n);
+    let mut sequences = Vec::with_capacity(n);
+    for i in 0..n {
+        let (line_number, line) = lines.next().ok_or_else(|| {
+            Error::parse(
+                last + 1,
+                format!("unexpected end of input, expected taxon {} of {n}", i + 1),
+            )
+        })?;
+        last = *line_number;
+        let (name, rest) = names_format.split(line);
+        let mut sequence: Vec<u8> = residues(rest).collect();
+        while sequence.len() < m {
+            let (line_number, line) = lines.next().ok_or_else(|| {
+                Error::parse(
+                    last + 1,
+                    format!("unexpected end of input, expected sites of '{name}'"),
+                )
+            })?;
+            last = *line_number;
+            sequence.extend(residues(line));
+        }
+        if sequence.len() != m {
+            return Err(Error::parse(
+                last,
+                format!("'{name}' has {} sites, expected {m}", sequence.len()),
+            ));
+        }
+        names.push(name.to_string());
+        sequences.push(sequence);
+    }
+    if let Some((line_number, _)) = lines.next() {
+        return Err(Error::parse(
+            *line_number,
+            "unexpected data after the last taxon",
+        ));
+    }
+    Alignment::build(names, sequences)
+}
+
+/// First block with names and the n taxa, following blocks with the next sites of every taxon
+fn read_interleaved(
+    lines: &Lines,
+    n: usize,
+    m: usize,
+    names_format: PhylipNames,
+) -> Result<Alignment> {
+    if lines.len() < n {
+        let last = lines.last().map_or(1, |(line_number, _)| *line_number);
+        return Err(Error::parse(
+            last + 1,
+            format!(
+                "unexpected end of input, expected taxon {} of {n}",
+                lines.len() + 1
+            ),
+        ));
+    }
+    let mut names = Vec::with_capacity(n);
+    let mut sequences: Vec<Vec<u8>> = Vec::with_capacity(n);
+    for (_, line) in &lines[..n] {
+        let (name, rest) = names_format.split(line);
+        names.push(name.to_string());
+        sequences.push(residues(rest).collect());
+    }
+    for (k, (_, line)) in lines.iter().enumerate().skip(n) {
+        sequences[k % n].extend(residues(line));
+    }
+    let last = lines.last().map_or(1, |(line_number, _)| *line_number);
+    for (name, sequence) in names.iter().zip(sequences.iter()) {
+        if sequence.len() != m {
+            return Err(Error::parse(
+                last,
+                format!("'{name}' has {} sites, expected {m}", sequence.len()),
+            ));
+        }
+    }
+    Alignment::build(names, sequences)
+}
+
+impl Alignment {
+    /// Read a sequential or interleaved [PHYLIP](https://phylipweb.github.io/phylip/) alignment.
+    /// The header may end with `S` or `I` to force the sequential or interleaved format, otherwise it is detected.
+    pub fn read_phylip<R>(reader: R, names_format: PhylipNames) -> Result<Alignment>
+    where
+        R: io::BufRead,
+    {
+        let mut lines = Vec::new();
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                lines.push((index + 1, line));
+            }
+        }
+        if lines.is_empty() {
+            return Err(Error::parse(1, "expected the number of taxa and sites"));
+        }
+        let (header_line, header) = lines.remove(0);
+        let mut words = header.split_whitespace();
+        let mut number = || words.next().and_then(|word| word.parse::<usize>().ok());
+        let (n, m) = number()
+            .zip(number())
+            .ok_or_else(|| Error::parse(header_line, "expected the number of taxa and sites"))?;
+        let option = header
+            .split_whitespace()
+            .nth(2)
+            .map(|word| word.to_ascii_uppercase());
+        match option.as_deref() {
+            Some(option) if option.starts_with('S') => read_sequential(&lines, n, m, names_format),
+            Some(option) if option.starts_with('I') => read_interleaved(&lines, n, m, names_format),
+            _ => read_sequential(&lines, n, m, names_format)
+                .or_else(|_| read_interleaved(&lines, n, m, names_format)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sequential() {
+        let input = "3 8
+alpha     ACGTACGT
+beta      ACGT
+ACGA
+gamma     ACGTTCGT
+"
+        .as_bytes();
+        let aln = Alignment::read_phylip(input, PhylipNames::Relaxed).unwrap();
+        assert_eq!(aln.names, vec!["alpha", "beta", "gamma"]);
+        assert_eq!(aln.sequences[1], b"ACGTACGA");
+    }
+
+    #[test]
+    fn test_interleaved() {
+        let input = "3 8
+alpha     ACGT
+beta      ACGT
+gamma     ACGT
+
+ACGT
+ACGA
+TCGT
+"
+        .as_bytes();
+        let aln = Alignment::read_phylip(input, PhylipNames::Relaxed).unwrap();
+        assert_eq!(aln.names, vec!["alpha", "beta", "gamma"]);
+        assert_eq!(aln.sequences[0], b"ACGTACGT");
+        assert_eq!(aln.sequences[1], b"ACGTACGA");
+        assert_eq!(aln.sequences[2], b"ACGTTCGT");
+    }
+
+    #[test]
+    fn test_strict_names() {
+        let input = "2 4 I
+Homo sapieACGT
+Pan       ACGA
+"
+        .as_bytes();
+        let aln = Alignment::read_phylip(input, PhylipNames::Strict).unwrap();
+        assert_eq!(aln.names, vec!["Homo sapie", "Pan"]);
+        assert_eq!(aln.sequences[0], b"ACGT");
+    }
+
+    #[test]
+    fn test_wrong_length() {
+        let input = "2 4 S
+a ACGT
+b ACGTA
+"
+        .as_bytes();
+        let err = Alignment::read_phylip(input, PhylipNames::Relaxed).unwrap_err();
+        assert_eq!(err.to_string(), "line 3: 'b' has 5 sites, expected 4");
+    }
+}
@@ -0,0 +1,92 @@
+use super::rates::RateVariation;
+
+/// The 20 amino acids, in the order of the empirical matrices (PAML order)
+pub(crate) const AMINO_ACIDS: &[u8; 20] = b"ARNDCQEGHILKMFPSTWYV";
+
+/// Index of an unambiguous amino acid. Gaps, stop codons and ambiguity codes (B, Z, J, X) are None.
+pub(crate) fn encode(c: u8) -> Option<usize> {
+    AMINO_ACIDS.iter().position(|aa| *aa == c)
+}
+
+/// Number of sites with amino acid x in the first sequence and y in the second one.
+/// Only sites with unambiguous amino acids in both sequences are counted (pairwise deletion).
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ProteinCounts {
+    pub counts: [[u32; 20]; 20],
+}
+
+impl ProteinCounts {
+    pub fn count(a: &[u8], b: &[u8]) -> Self {
+        let mut counts = [[0; 20]; 20];
+        for (x, y) in a.iter().zip(b.iter()) {
+            if let (Some(x), Some(y)) = (encode(*x), encode(*y)) {
+                counts[x][y] += 1;
+            }
+        }
+        ProteinCounts { counts }
+    }
+    pub fn sites(&self) -> u32 {
+        self.counts.iter().flatten().sum()
+    }
+    /// Proportion of differing sites
+    pub fn differences(&self) -> f64 {
+        let same: u32 = (0..20).map(|x| self.counts[x][x]).sum();
+        (self.sites() - same) as f64 / self.sites() as f64
+    }
+}
+
+pub(crate) fn p_distance(c: &ProteinCounts) -> Option<f64> {
+    if c.sites() == 0 {
+        return Some(1.0);
+    }
+    Some(c.differences())
+}
+
+/// Poisson correction, -ln(1 - p)
+pub(crate) fn poisson(c: &ProteinCounts, rates: &RateVariation) -> Option<f64> {
+    if c.sites() == 0 {
+        return None;
+    }
+    rates.neg_log(1.0 - c.differences())
+}
+
+/// Kimura (1983) approximation, -ln(1 - p - 0.2 p^2), as in QuickTree and RapidNJ
+/// With rate variation, the proportion of differences is taken over the variable sites.
+pub(crate) fn kimura(c: &ProteinCounts, rates: &RateVariation) -> Option<f64> {
+    if c.sites() == 0 {
+        return None;
+    }
+    let variable = 1.0 - rates.pinv;
+    let p = c.differences() / variable;
+    Some(variable * rates.correct(1.0 - p - 0.2 * p * p)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counts(a: &str, b: &str) -> ProteinCounts {
+        ProteinCounts::count(a.as_bytes(), b.as_bytes())
+    }
+
+    #[test]
+    fn test_counts_ignore_ambiguity_codes() {
+        let c = counts("MDPXLA-R", "MEPELAKB");
+        assert_eq!(c.sites(), 5);
+        assert_eq!(c.differences(), 1.0 / 5.0);
+    }
+
+    #[test]
+    fn test_corrections() {
+        let rates = RateVariation::default();
+        let c = counts("MDPELAKRWV", "MEPELAKRWY");
+        let p: f64 = 0.2;
+        assert_eq!(p_distance(&c), Some(p));
+        assert!((poisson(&c, &rates).unwrap() + (1.0 - p).ln()).abs() < 1e-12);
+        assert!((kimura(&c, &rates).unwrap() + (1.0 - p - 0.2 * p * p).ln()).abs() < 1e-12);
+        // Kimura's approximation saturates before p = 1
+        let c = counts("MDPELAKRWV", "AEKDMCRIWY");
+        assert_eq!(kimura(&c, &rates), None);
+        assert!(poisson(&c, &rates).is_some());
+    }
+}
@@ -0,0 +1,203 @@
+/// Rate variation across sites: a gamma distribution of rates with shape alpha (None for equal rates)
+/// and a proportion of invariant sites
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct RateVariation {
+    pub alpha: Option<f64>,
+    pub pinv: f64,
+}
+
+/// Number of categories of the discrete gamma distribution (Yang 1994)
+const GAMMA_CATEGORIES: usize = 4;
+
+impl RateVariation {
+    /// Whether every site evolves at the same rate
+    pub fn is_uniform(&self) -> bool {
+        self.alpha.is_none() && self.pinv == 0.0
+    }
+    /// Correction of the variable sites: -ln(x), or alpha (x^(-1/alpha) - 1) with gamma rates.
+    /// It is undefined (the pair is saturated) when x is not positive.
+    pub fn correct(&self, x: f64) -> Option<f64> {
+        if x <= 0.0 {
+            return None;
+        }
+        Some(match self.alpha {
+            None => -x.ln(),
+            Some(alpha) => alpha * (x.powf(-1.0 / alpha) - 1.0),
+        })
+    }
+    /// Generalization of -ln(x) for corrections of the form -ln(1 - a p - b q - ...).
+    /// The proportions are taken over the variable sites and the distance is scaled back to all sites.
+    pub fn neg_log(&self, x: f64) -> Option<f64> {
+        let x = 1.0 - (1.0 - x) / (1.0 - self.pinv);
+        Some((1.0 - self.pinv) * self.correct(x)?)
+    }
+    /// Probability and relative rate of every category of sites
+    pub fn categories(&self) -> Vec<(f64, f64)> {
+        let variable = 1.0 - self.pinv;
+        let mut categories = Vec::new();
+        if self.pinv > 0.0 {
+            categories.push((self.pinv, 0.0));
+        }
+        match self.alpha {
+            None => categories.push((variable, 1.0 / variable)),
+            Some(alpha) => {
+                let k = GAMMA_CATEGORIES as f64;
+                categories.extend(
+                    discrete_gamma(alpha, GAMMA_CATEGORIES)
+                        .into_iter()
+                        .map(|rate| (variable / k, rate / variable)),
+                );
+            }
+        }
+        categories
+    }
+}
+
+/// Mean rate of each of the k equiprobable categories of a gamma distribution with mean 1 (Yang 1994)
+fn discrete_gamma(alpha: f64, k: usize) -> Vec<f64> {
+    // Cut points of the categories, for a gamma distribution with shape and rate alpha
+    let mut cuts = vec![0.0];
+    cuts.extend((1..k).map(|i| gamma_quantile(alpha, i as f64 / k as f64) / alpha));
+    let mut below = vec![0.0];
+    below.extend(
+        cuts[1..]
+            .iter()
+            .map(|cut| lower_gamma(alpha + 1.0, alpha * cut)),
+    );
+    below.push(1.0);
+    below.windows(2).map(|w| (w[1] - w[0]) * k as f64).collect()
+}
+
+/// Quantile of a gamma distribution with the given shape and scale 1, by bisection
+fn gamma_quantile(shape: f64, probability: f64) -> f64 {
+    let (mut low, mut high) = (0.0, shape.max(1.0));
+    while lower_gamma(shape, high) < probability {
+        high *= 2.0;
+    }
+    for _ in 0..200 {
+        let mid = (low + high) / 2.0;
+        if lower_gamma(shape, mid) < probability {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    (low + high) / 2.0
+}
+
+/// Regularized lower incomplete gamma function P(a, x)
+fn lower_gamma(a: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    let prefactor = (a * x.ln() - x - ln_gamma(a)).exp();
+    if x < a + 1.0 {
+        // Series expansion
+        let (mut term, mut sum, mut n) = (1.0 / a, 1.0 / a, a);
+        while term.abs() > sum.abs() * 1e-15 {
+            n += 1.0;
+            term *= x / n;
+            sum += term;
+        }
+        sum * prefactor
+    } else {
+        // Continued fraction of the upper function (modified Lentz)
+        let tiny = 1e-300;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / tiny;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for i in 1..1000 {
+            let an = -(i as f64) * (i as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < tiny {
+                d = tiny;
+            }
+            c = b + an / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            d = 1.0 / d;
+            let delta = d * c;
+            h *= delta;
+            if (delta - 1.0).abs() < 1e-15 {
+                break;
+            }
+        }
+        1.0 - prefactor * h
+    }
+}
+
+/// Logarithm of the gamma function (Lanczos approximation)
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.18009172947146,
+        -86.50532032941677,
+        24.01409824083091,
+        -1.231739572450155,
+        0.1208650973866179e-2,
+        -0.5395239384953e-5,
+    ];
+    let tmp = x + 5.5;
+    let tmp = tmp - (x + 0.5) * tmp.ln();
+    let series = COEFFICIENTS
+        .iter()
+        .enumerate()
+        .fold(1.000000000190015, |sum, (i, c)| {
+            sum + c / (x + 1.0 + i as f64)
+        });
+    -tmp + (2.5066282746310005 * series / x).ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_incomplete_gamma() {
+        assert!((ln_gamma(5.0) - 24f64.ln()).abs() < 1e-10);
+        // Exponential distribution
+        assert!((lower_gamma(1.0, 2.0) - (1.0 - (-2f64).exp())).abs() < 1e-12);
+        assert!((lower_gamma(1.0, 0.5) - (1.0 - (-0.5f64).exp())).abs() < 1e-12);
+        assert!((gamma_quantile(1.0, 0.5) - 2f64.ln()).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_discrete_gamma() {
+        // Yang (1994), alpha = 0.5 and four categories
+        let rates = discrete_gamma(0.5, 4);
+        let expected = [0.0334, 0.2519, 0.8203, 2.8944];
+        for (rate, expected) in rates.iter().zip(expected.iter()) {
+            assert!((rate - expected).abs() < 1e-3);
+        }
+        assert!((rates.iter().sum::<f64>() / 4.0 - 1.0).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_corrections() {
+        let uniform = RateVariation::default();
+        assert_eq!(uniform.neg_log(0.5), Some(-(0.5f64.ln())));
+        let gamma = RateVariation {
+            alpha: Some(1.0),
+            pinv: 0.0,
+        };
+        assert_eq!(gamma.neg_log(0.5), Some(1.0));
+        // Gamma corrections are larger, and tend to -ln(x) for large alpha
+        assert!(gamma.neg_log(0.5).unwrap() > uniform.neg_log(0.5).unwrap());
+        let flat = RateVariation {
+            alpha: Some(1e6),
+            pinv: 0.0,
+        };
+        assert!((flat.neg_log(0.5).unwrap() - uniform.neg_log(0.5).unwrap()).abs() < 1e-5);
+        let invariant = RateVariation {
+            alpha: None,
+            pinv: 0.5,
+        };
+        // Half of the sites are invariant, so 1 - x of the variable sites is twice as large
+        assert_eq!(invariant.neg_log(0.75), Some(-0.5 * 0.5f64.ln()));
+        assert_eq!(invariant.neg_log(0.5), None);
+        let categories = invariant.categories();
+        assert_eq!(categories, vec![(0.5, 0.0), (0.5, 2.0)]);
+    }
+}
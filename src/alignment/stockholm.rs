@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+use std::io;
+
+use super::Alignment;
+use crate::{Error, Result};
+
+impl Alignment {
+    /// Read a [Stockholm](https://en.wikipedia.org/wiki/Stockholm_format) alignment (as the Pfam ones).
+    /// Annotation lines are ignored and sequences split in several blocks are joined.
+    pub fn read_stockholm<R>(reader: R) -> Result<Alignment>
+    where
+        R: io::BufRead,
+    {
+        let mut names = Vec::new();
+        let mut sequences: Vec<Vec<u8>> = Vec::new();
+        let mut index_of = HashMap::new();
+        let mut header = false;
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line_number = index + 1;
+            let line = line.trim();
+            if !header {
+                if line.is_empty() {
+                    continue;
+                }
+                if !line.starts_with("# STOCKHOLM") {
+                    return Err(Error::parse(line_number, "expected '# STOCKHOLM' header"));
+                }
+                header = true;
+                continue;
+            }
+            if line == "//" {
+                break;
+            }
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut words = line.split_whitespace();
+            let name = words.next().expect("Non-empty line");
+            let residues = words.next().ok_or_else(|| {
+                Error::parse(line_number, format!("missing sequence of '{name}'"))
+            })?;
+            if words.next().is_some() {
+                return Err(Error::parse(
+                    line_number,
+                    "expected a sequence name followed by the aligned sequence",
+                ));
+            }
+            let i = *index_of.entry(name.to_string()).or_insert_with(|| {
+                names.push(name.to_string());
+                sequences.push(Vec::new());
+                names.len() - 1
+            });
+            sequences[i].extend_from_slice(residues.as_bytes());
+        }
+        if !header {
+            return Err(Error::parse(1, "expected '# STOCKHOLM' header"));
+        }
+        Alignment::build(names, sequences)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_stockholm() {
+        let input = "# STOCKHOLM 1.0
+#=GS a/1-10 AC P1
+a/1-10   MDP-LA
+b/2-9    MDPQ..
+#=GC SS_cons ......
+
+a/1-10   KR
+b/2-9    K-
+//
+"
+        .as_bytes();
+        let aln = Alignment::read_stockholm(input).unwrap();
+        assert_eq!(aln.names, vec!["a/1-10", "b/2-9"]);
+        assert_eq!(aln.sequences[0], b"MDP-LAKR");
+        assert_eq!(aln.sequences[1], b"MDPQ..K-");
+    }
+
+    #[test]
+    fn test_read_bundled_example() {
+        let file = std::fs::File::open("data/58_AAR2.sth").unwrap();
+        let aln = Alignment::read_stockholm(io::BufReader::new(file)).unwrap();
+        assert_eq!(aln.len(), 58);
+        assert_eq!(aln.names[0], "CT004_HUMAN/6-365");
+    }
+
+    #[test]
+    fn test_missing_header() {
+        let input = "a MDP\n".as_bytes();
+        let err = Alignment::read_stockholm(input).unwrap_err();
+        assert_eq!(err.to_string(), "line 1: expected '# STOCKHOLM' header");
+    }
+}
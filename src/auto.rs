@@ -0,0 +1,182 @@
+use std::fmt;
+
+use crate::{triangular::LowerTriangle, Error, Precision, Result, RowStorage};
+
+/// Number of taxa up to which Canonical is faster than the sorted rows of RapidBtrees, which take
+/// longer to build than to search. Hybrid switches to Canonical when this many taxa are left.
+const CANONICAL_LIMIT: usize = 2000;
+/// Chunks searched by every thread in the RapidBtrees phase, so that a thread whose rows are pruned
+/// early can take the chunks of another one
+const CHUNKS_PER_THREAD: usize = 4;
+
+/// Strategy chosen by the [`Auto`](crate::Auto) solver
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// Canonical neighbor joining
+    Canonical,
+    /// RapidBtrees, and then Canonical for the last `canonical_iters` iterations
+    Hybrid {
+        /// Rows searched by every worker
+        chunk_size: usize,
+        /// Number of iterations done by Canonical, when as many taxa as those plus 3 are left
+        canonical_iters: usize,
+        /// Storage of the sorted rows
+        row_storage: RowStorage,
+    },
+}
+
+/// Choice of the [`Auto`](crate::Auto) solver
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Plan {
+    /// Strategy and its parameters
+    pub strategy: Strategy,
+    /// Precision of the distances
+    pub precision: Precision,
+    /// Approximate peak memory of the solver in bytes, without the distance matrix it is given
+    pub memory: usize,
+}
+
+impl Plan {
+    /// Fastest strategy for n taxa and the given threads. If it does not fit in the memory budget,
+    /// it is tried in single precision (unless the precision is given), and then Canonical.
+    pub(crate) fn choose(
+        n: usize,
+        threads: usize,
+        memory_budget: Option<usize>,
+        precision: Option<Precision>,
+    ) -> Result<Plan> {
+        let fastest = if n <= CANONICAL_LIMIT {
+            Strategy::Canonical
+        } else {
+            Strategy::Hybrid {
+                chunk_size: std::cmp::max(n / (CHUNKS_PER_THREAD * threads.max(1)), 1),
+                canonical_iters: CANONICAL_LIMIT - 3,
+                row_storage: RowStorage::SortedArray,
+            }
+        };
+        let precisions = match precision {
+            Some(precision) => vec![precision],
+            None => vec![Precision::Double, Precision::Single],
+        };
+        let mut smallest = None;
+        for strategy in [fastest, Strategy::Canonical] {
+            for precision in precisions.iter().copied() {
+                let plan = Plan {
+                    strategy,
+                    precision,
+                    memory: memory(strategy, precision, n),
+                };
+                match memory_budget {
+                    // The last plan tried is the smallest one
+                    Some(budget) if plan.memory > budget => smallest = Some(plan.memory),
+                    _ => return Ok(plan),
+                }
+            }
+        }
+        Err(Error::InvalidParameter(format!(
+            "a memory budget of {} is too small for {n} taxa, which need at least {}",
+            megabytes(memory_budget.unwrap_or_default()),
+            megabytes(smallest.unwrap_or_default())
+        )))
+    }
+}
+
+/// Peak memory of a strategy, from the bytes per pair of taxa measured on a few thousand taxa
+fn memory(strategy: Strategy, precision: Precision, n: usize) -> usize {
+    let bytes_per_pair = precision.width()
+        + match strategy {
+            Strategy::Canonical => 0,
+            Strategy::Hybrid { row_storage, .. } => match row_storage {
+                RowStorage::SortedArray => 10,
+                RowStorage::BTree => 22,
+            },
+        };
+    LowerTriangle::<f64>::len(n).saturating_mul(bytes_per_pair)
+}
+
+fn megabytes(bytes: usize) -> String {
+    format!("{:.1} MB", bytes as f64 / 1e6)
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.strategy {
+            Strategy::Canonical => write!(f, "canonical")?,
+            Strategy::Hybrid {
+                chunk_size,
+                canonical_iters,
+                row_storage,
+            } => {
+                let rows = match row_storage {
+                    RowStorage::BTree => "B-trees",
+                    RowStorage::SortedArray => "sorted arrays",
+                };
+                write!(
+                    f,
+                    "hybrid (rapidnj with {rows} until {} taxa are left, then canonical), chunk size {chunk_size}",
+                    canonical_iters + 3
+                )?
+            }
+        }
+        let precision = match self.precision {
+            Precision::Double => "double",
+            Precision::Single => "single",
+        };
+        write!(
+            f,
+            ", {precision} precision, about {}",
+            megabytes(self.memory)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strategy_follows_size_and_threads() {
+        let plan = Plan::choose(500, 8, None, None).unwrap();
+        assert_eq!(plan.strategy, Strategy::Canonical);
+        assert_eq!(plan.precision, Precision::Double);
+        assert_eq!(plan.memory, 500 * 499 / 2 * 8);
+        let plan = Plan::choose(10_000, 5, None, None).unwrap();
+        assert_eq!(
+            plan.strategy,
+            Strategy::Hybrid {
+                chunk_size: 500,
+                canonical_iters: CANONICAL_LIMIT - 3,
+                row_storage: RowStorage::SortedArray
+            }
+        );
+        assert!(plan
+            .to_string()
+            .starts_with("hybrid (rapidnj with sorted arrays until 2000"));
+        assert!(plan
+            .to_string()
+            .ends_with("double precision, about 899.9 MB"));
+    }
+
+    #[test]
+    fn test_memory_budget() {
+        let n = 10_000;
+        let pairs = n * (n - 1) / 2;
+        // Single precision comes before Canonical, which takes less memory than the sorted rows
+        let plan = Plan::choose(n, 1, Some(pairs * 16), None).unwrap();
+        assert!(matches!(plan.strategy, Strategy::Hybrid { .. }));
+        assert_eq!(plan.precision, Precision::Single);
+        let plan = Plan::choose(n, 1, Some(pairs * 8), None).unwrap();
+        assert_eq!(plan.strategy, Strategy::Canonical);
+        assert_eq!(plan.precision, Precision::Double);
+        let plan = Plan::choose(n, 1, Some(pairs * 4), None).unwrap();
+        assert_eq!(plan.strategy, Strategy::Canonical);
+        assert_eq!(plan.precision, Precision::Single);
+        // A given precision is kept
+        let plan = Plan::choose(n, 1, Some(pairs * 16), Some(Precision::Double)).unwrap();
+        assert_eq!(plan.strategy, Strategy::Canonical);
+        let err = Plan::choose(n, 1, Some(pairs * 7), Some(Precision::Double)).unwrap_err();
+        assert!(matches!(err, Error::InvalidParameter(_)));
+        let err = Plan::choose(n, 1, Some(pairs), None).unwrap_err();
+        assert!(err.to_string().contains("need at least 200.0 MB"), "{err}");
+    }
+}
@@ -1,20 +1,27 @@
 extern crate speedytree;
-use clap::Parser;
+use clap::{ArgGroup, Parser, ValueEnum};
 /// # speedytree
 /// `speedytree` is a command line tool for quickly creating a directory tree.
 /// It is a Rust implementation of the `tree` command line tool.
 /// It is intended to be a drop-in replacement for the `tree` command.
 /// It is not intended to be a complete implementation of the `tree` command.
 /// It is intended to be a fast implementation of the `tree` command.
-use speedytree::DistanceMatrix;
-use speedytree::{Canonical, Hybrid, NeighborJoiningSolver, RapidBtrees};
+use speedytree::{
+    Alignment, DistanceCalculator, DistanceMatrix, DistanceModel, Error, Linkage, NegativeBranches,
+    PhylipNames, Precision, RowStorage, SketchMethod, Sketcher, ValidationAction, ValidationPolicy,
+};
+use speedytree::{
+    Auto, BioNJ, Canonical, Clustering, Hybrid, NeighborJoiningSolver, RapidBtrees, Solution,
+    Unweighted,
+};
 
+use rayon::prelude::*;
 use std::{
-    error,
-    io::{self, Write},
+    fs::File,
+    io::{self, BufReader, Write},
+    path::PathBuf,
     process,
 };
-type ResultBox<T> = std::result::Result<T, Box<dyn error::Error>>;
 
 /// Define the configuration of the program
 /// It contains the algorithm to use and the number of threads to use
@@ -24,17 +31,38 @@ pub struct Config {
     pub(crate) algo: Algorithm,
     pub(crate) threads: usize,
     pub(crate) chunk_size: usize,
+    pub(crate) row_storage: RowStorage,
     pub(crate) naive_percentage: usize,
+    pub(crate) adaptive_switch: bool,
+    pub(crate) memory_budget: Option<usize>,
+    pub(crate) names: PhylipNames,
+    pub(crate) input_format: InputFormat,
+    pub(crate) calculator: DistanceCalculator,
+    pub(crate) genomes: Vec<PathBuf>,
+    pub(crate) sketcher: Sketcher,
+    pub(crate) validation: Option<ValidationPolicy>,
+    pub(crate) negative_branches: NegativeBranches,
+    pub(crate) precision: Precision,
+    pub(crate) binary: Option<PathBuf>,
+    pub(crate) convert: Option<PathBuf>,
 }
 
 impl Config {
     /// Build the configuration from the command line arguments
-    pub fn build(args: Args) -> ResultBox<Config> {
+    pub fn build(args: Args) -> Result<Config, Error> {
         // Let match the algorithm, if not specified, use Naive
         let algo = if args.naive {
             Algorithm::Naive
         } else if args.rapidnj {
             Algorithm::RapidNJ
+        } else if args.bionj {
+            Algorithm::BioNJ
+        } else if args.unj {
+            Algorithm::Unweighted
+        } else if let Some(linkage) = args.linkage {
+            Algorithm::Clustering(linkage.into())
+        } else if args.auto {
+            Algorithm::Auto
         } else {
             Algorithm::Hybrid
         };
@@ -42,22 +70,73 @@ impl Config {
         let chunk_size = args.chunk_size;
         // If chunk size is 0, error
         if chunk_size == 0 {
-            return Err("Chunk size cannot be 0".into());
+            return Err(Error::InvalidParameter(
+                "chunk size cannot be 0".to_string(),
+            ));
         }
         let naive_percentage = args.naive_percentage;
-        // If naive percentage is 0, error
-        if naive_percentage == 0 {
-            return Err("Naive percentage cannot be 0".into());
+        if naive_percentage > 100 {
+            return Err(Error::InvalidParameter(
+                "naive percentage cannot be greater than 100".to_string(),
+            ));
         }
-        // If naive percentage is 100, error
-        if naive_percentage == 100 {
-            return Err("Naive percentage cannot be 100".into());
+        let names = if args.strict_names {
+            PhylipNames::Strict
+        } else {
+            PhylipNames::Relaxed
+        };
+        let mut calculator = DistanceCalculator::default(args.model.into())
+            .set_max_distance(args.max_distance)?
+            .set_invariant_sites(args.pinv)?;
+        if let Some(alpha) = args.gamma {
+            calculator = calculator.set_gamma(alpha)?;
         }
+        let method = if args.sketch_size == 0 {
+            SketchMethod::Exact
+        } else {
+            SketchMethod::MinHash {
+                sketch_size: args.sketch_size,
+            }
+        };
+        let sketcher = Sketcher::default()
+            .set_kmer_size(args.kmer_size)?
+            .set_method(method)?;
+        let validation = if args.no_validate {
+            None
+        } else {
+            let action = if args.warn_invalid {
+                ValidationAction::Warn
+            } else {
+                ValidationAction::Error
+            };
+            Some(ValidationPolicy {
+                action,
+                symmetrize: args.symmetrize,
+                clamp_negatives: args.clamp_negatives,
+            })
+        };
         Ok(Config {
             algo,
             threads: cores,
             chunk_size,
+            row_storage: args.row_storage.into(),
             naive_percentage,
+            adaptive_switch: args.adaptive_switch,
+            memory_budget: args.memory_budget,
+            names,
+            input_format: args.input_format,
+            calculator,
+            genomes: args.genomes,
+            sketcher,
+            validation,
+            negative_branches: args.negative_branches.into(),
+            precision: if args.f32 {
+                Precision::Single
+            } else {
+                Precision::Double
+            },
+            binary: args.binary,
+            convert: args.convert,
         })
     }
 }
@@ -65,33 +144,300 @@ impl Config {
 /// Define the command line arguments
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
+#[command(group(ArgGroup::new("algorithm").args(["rapidnj", "naive", "hybrid", "bionj", "unj", "linkage", "auto"])))]
 pub struct Args {
     /// Use the rapidnj heuristic
-    #[arg(long, conflicts_with = "hybrid", conflicts_with = "naive")]
+    #[arg(long)]
     rapidnj: bool,
     /// Use the naive algorithm
-    #[arg(long, conflicts_with = "hybrid", conflicts_with = "rapidnj")]
+    #[arg(long)]
     naive: bool,
     /// Use the hybrid heuristic
-    #[arg(long, conflicts_with = "rapidnj", conflicts_with = "naive")]
+    #[arg(long)]
     hybrid: bool,
+    /// Use BIONJ (Gascuel 1997)
+    #[arg(long)]
+    bionj: bool,
+    /// Use unweighted neighbor joining, UNJ (Gascuel 1997)
+    #[arg(long)]
+    unj: bool,
+    /// Build a rooted ultrametric tree by agglomerative clustering with the given linkage
+    #[arg(long, value_enum)]
+    linkage: Option<LinkageMethod>,
+    /// Choose the neighbor joining strategy from the size of the matrix and the number of cores
+    #[arg(long)]
+    auto: bool,
+    /// Memory in megabytes the auto strategy may use, besides the distance matrix
+    #[arg(long, requires = "auto", value_parser = parse_megabytes)]
+    memory_budget: Option<usize>,
     /// Number of cores to use
     /// Default: 1
     #[arg(short, long, default_value = "1")]
     cores: usize,
     /// Chunk size to be handled by each thread
     /// Default: 30
-    #[arg(long, default_value = "30", conflicts_with = "naive")]
+    #[arg(
+        long,
+        default_value = "30",
+        conflicts_with = "naive",
+        conflicts_with = "bionj",
+        conflicts_with = "unj",
+        conflicts_with = "linkage",
+        conflicts_with = "auto"
+    )]
     chunk_size: usize,
-    /// Percentage of the matrix to be handled by the naive algorithm
+    /// How the rapidnj and hybrid heuristics store their sorted rows
+    #[arg(
+        long,
+        value_enum,
+        default_value = "btree",
+        conflicts_with = "naive",
+        conflicts_with = "bionj",
+        conflicts_with = "unj",
+        conflicts_with = "linkage",
+        conflicts_with = "auto"
+    )]
+    row_storage: Rows,
+    /// Percentage of the iterations done by the naive algorithm, at the end
+    /// (0 for the rapidnj heuristic only, 100 for the naive algorithm only).
+    /// It used to be the taxa left when switching to the naive algorithm, as a percentage of all the taxa.
     /// Default: 90
     #[arg(
         long,
         default_value = "90",
         conflicts_with = "naive",
-        conflicts_with = "rapidnj"
+        conflicts_with = "rapidnj",
+        conflicts_with = "bionj",
+        conflicts_with = "unj",
+        conflicts_with = "linkage",
+        conflicts_with = "auto"
     )]
     naive_percentage: usize,
+    /// Switch the hybrid heuristic to the naive algorithm when it becomes cheaper, instead of
+    /// after a fixed percentage of the matrix
+    #[arg(
+        long,
+        conflicts_with = "naive_percentage",
+        conflicts_with = "naive",
+        conflicts_with = "rapidnj",
+        conflicts_with = "bionj",
+        conflicts_with = "unj",
+        conflicts_with = "linkage",
+        conflicts_with = "auto"
+    )]
+    adaptive_switch: bool,
+    /// Read taxon names as the first 10 characters of every row (strict PHYLIP)
+    #[arg(long)]
+    strict_names: bool,
+    /// Format of the input read from stdin. Alignments are converted to a distance matrix first.
+    #[arg(long, value_enum, default_value = "phylip")]
+    input_format: InputFormat,
+    /// Evolutionary model used to compute distances from an alignment
+    #[arg(long, value_enum, default_value = "p-distance")]
+    model: Model,
+    /// Distance given to saturated pairs, where the model correction is undefined
+    #[arg(long, default_value = "10")]
+    max_distance: f64,
+    /// Shape (alpha) of the gamma distribution of rates across sites
+    #[arg(long)]
+    gamma: Option<f64>,
+    /// Proportion of invariant sites
+    #[arg(long, default_value = "0")]
+    pinv: f64,
+    /// Unaligned genomes (FASTA files, one per genome) to compare with k-mers instead of reading stdin
+    #[arg(long, num_args = 1.., conflicts_with = "input_format")]
+    genomes: Vec<PathBuf>,
+    /// Size of the k-mers of the genomes
+    #[arg(long, default_value = "21", requires = "genomes")]
+    kmer_size: usize,
+    /// Number of hashes of the MinHash sketch of every genome, 0 to compare every k-mer
+    #[arg(long, default_value = "1000", requires = "genomes")]
+    sketch_size: usize,
+    /// Do not validate the distance matrix before solving
+    #[arg(long)]
+    no_validate: bool,
+    /// Only warn about problems in the distance matrix instead of failing
+    #[arg(long, conflicts_with = "no_validate")]
+    warn_invalid: bool,
+    /// Replace asymmetric pairs of distances by their average
+    #[arg(long, conflicts_with = "no_validate")]
+    symmetrize: bool,
+    /// Replace negative distances by zero
+    #[arg(long, conflicts_with = "no_validate")]
+    clamp_negatives: bool,
+    /// What to do with negative branch lengths of the tree
+    #[arg(long, value_enum, default_value = "keep")]
+    negative_branches: BranchPolicy,
+    /// Solve with single-precision (f32) distances, which halves the memory of the naive, rapidnj, hybrid and auto algorithms
+    #[arg(
+        long,
+        conflicts_with = "bionj",
+        conflicts_with = "unj",
+        conflicts_with = "linkage"
+    )]
+    f32: bool,
+    /// Read a binary distance matrix (written by --convert) through a memory map instead of stdin
+    #[arg(long, conflicts_with = "input_format", conflicts_with = "genomes")]
+    binary: Option<PathBuf>,
+    /// Write the distance matrix to this file in the binary format instead of building a tree
+    /// (in single precision with --f32)
+    #[arg(long)]
+    convert: Option<PathBuf>,
+}
+
+/// Formats of the input
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum InputFormat {
+    /// PHYLIP distance matrix
+    Phylip,
+    /// Stockholm alignment
+    Sth,
+    /// Aligned FASTA
+    Fasta,
+    /// Sequential or interleaved PHYLIP alignment
+    PhylipAlignment,
+}
+
+/// Distance models of the command line
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Model {
+    /// Proportion of differing sites
+    PDistance,
+    /// Jukes and Cantor
+    Jc,
+    /// Kimura two-parameter
+    K2p,
+    /// Tamura and Nei
+    Tn,
+    /// Felsenstein's F84
+    F84,
+    /// LogDet / paralinear
+    Logdet,
+    /// Poisson correction for proteins
+    Poisson,
+    /// Kimura's approximation for proteins
+    Kimura,
+    /// Maximum likelihood under JTT
+    Jtt,
+    /// Maximum likelihood under WAG
+    Wag,
+    /// Maximum likelihood under LG
+    Lg,
+}
+
+impl From<Model> for DistanceModel {
+    fn from(model: Model) -> Self {
+        match model {
+            Model::PDistance => DistanceModel::PDistance,
+            Model::Jc => DistanceModel::JukesCantor,
+            Model::K2p => DistanceModel::Kimura2P,
+            Model::Tn => DistanceModel::TamuraNei,
+            Model::F84 => DistanceModel::F84,
+            Model::Logdet => DistanceModel::LogDet,
+            Model::Poisson => DistanceModel::Poisson,
+            Model::Kimura => DistanceModel::KimuraProtein,
+            Model::Jtt => DistanceModel::Jtt,
+            Model::Wag => DistanceModel::Wag,
+            Model::Lg => DistanceModel::Lg,
+        }
+    }
+}
+
+/// Storage of the sorted rows in the command line
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Rows {
+    /// A B-tree per row
+    Btree,
+    /// A sorted array per row, with a third of the memory per entry
+    SortedArray,
+}
+
+impl From<Rows> for RowStorage {
+    fn from(rows: Rows) -> Self {
+        match rows {
+            Rows::Btree => RowStorage::BTree,
+            Rows::SortedArray => RowStorage::SortedArray,
+        }
+    }
+}
+
+/// Linkages of the agglomerative clustering in the command line
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum LinkageMethod {
+    /// Average distance between the members of the clusters
+    Upgma,
+    /// Average distance of the merged clusters
+    Wpgma,
+    /// Smallest distance between the members of the clusters
+    Single,
+    /// Largest distance between the members of the clusters
+    Complete,
+}
+
+impl From<LinkageMethod> for Linkage {
+    fn from(linkage: LinkageMethod) -> Self {
+        match linkage {
+            LinkageMethod::Upgma => Linkage::Upgma,
+            LinkageMethod::Wpgma => Linkage::Wpgma,
+            LinkageMethod::Single => Linkage::Single,
+            LinkageMethod::Complete => Linkage::Complete,
+        }
+    }
+}
+
+/// Policies for negative branch lengths in the command line
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum BranchPolicy {
+    /// Keep them negative
+    Keep,
+    /// Set them to zero
+    Zero,
+    /// Set them to zero and add the difference to the sibling branch
+    Transfer,
+}
+
+impl From<BranchPolicy> for NegativeBranches {
+    fn from(policy: BranchPolicy) -> Self {
+        match policy {
+            BranchPolicy::Keep => NegativeBranches::Keep,
+            BranchPolicy::Zero => NegativeBranches::Zero,
+            BranchPolicy::Transfer => NegativeBranches::Transfer,
+        }
+    }
+}
+
+/// Sketch every genome file, named after the file without extension
+fn sketch_genomes(config: &Config) -> Result<DistanceMatrix, Error> {
+    let sketches = config
+        .genomes
+        .par_iter()
+        .map(|path| {
+            let name = path
+                .file_stem()
+                .map_or_else(|| path.to_string_lossy(), |stem| stem.to_string_lossy());
+            let file = File::open(path)?;
+            config.sketcher.sketch_fasta(&name, BufReader::new(file))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    config.sketcher.distance_matrix(&sketches)
+}
+
+/// Read the distance matrix from stdin, computing it first if the input is an alignment
+fn read_distance_matrix(config: &Config) -> Result<DistanceMatrix, Error> {
+    if !config.genomes.is_empty() {
+        return sketch_genomes(config);
+    }
+    if let Some(path) = &config.binary {
+        return DistanceMatrix::read_binary(path);
+    }
+    let reader = io::stdin().lock();
+    let alignment = match config.input_format {
+        InputFormat::Phylip => return DistanceMatrix::read_from_phylip_with(reader, config.names),
+        InputFormat::Sth => Alignment::read_stockholm(reader)?,
+        InputFormat::Fasta => Alignment::read_fasta(reader)?,
+        InputFormat::PhylipAlignment => Alignment::read_phylip(reader, config.names)?,
+    };
+    config.calculator.compute(&alignment)
 }
 
 /// Available algorithms in the program
@@ -103,6 +449,53 @@ pub enum Algorithm {
     RapidNJ,
     /// Hybrid neighbor joining
     Hybrid,
+    /// BIONJ
+    BioNJ,
+    /// Unweighted neighbor joining
+    Unweighted,
+    /// Agglomerative clustering with the given linkage
+    Clustering(Linkage),
+    /// Strategy chosen from the size of the problem
+    Auto,
+}
+
+/// Solve with the hybrid heuristic, which switches to the naive algorithm for the last
+/// `naive_percentage` of the iterations, or when it becomes cheaper with `adaptive_switch`
+fn solve_hybrid(d: DistanceMatrix, config: &Config) -> Result<Solution, Error> {
+    let solver = NeighborJoiningSolver::<Hybrid>::default(d)
+        .set_chunk_size(config.chunk_size)?
+        .set_canonical_percentage(config.naive_percentage as f64 / 100.0)?
+        .set_row_storage(config.row_storage)
+        .set_precision(config.precision)
+        .set_negative_branches(config.negative_branches);
+    if config.adaptive_switch {
+        solver.set_adaptive_switch().solve_with_report()
+    } else {
+        solver.solve_with_report()
+    }
+}
+
+/// Parse a number of megabytes into bytes, rejecting the ones that do not fit in memory
+fn parse_megabytes(value: &str) -> Result<usize, String> {
+    let megabytes: usize = value.parse().map_err(|err| format!("{err}"))?;
+    megabytes
+        .checked_mul(1_000_000)
+        .ok_or_else(|| format!("{megabytes} MB is more memory than can be addressed"))
+}
+
+/// Solve with the strategy chosen by the auto solver, which is reported on stderr
+fn solve_auto(d: DistanceMatrix, config: &Config) -> Result<Solution, Error> {
+    let mut solver =
+        NeighborJoiningSolver::<Auto>::default(d).set_negative_branches(config.negative_branches);
+    if let Some(bytes) = config.memory_budget {
+        solver = solver.set_memory_budget(bytes)?;
+    }
+    // Single precision is only forced by --f32, otherwise it depends on the memory budget
+    if config.precision == Precision::Single {
+        solver = solver.set_precision(Precision::Single);
+    }
+    eprintln!("Auto strategy: {}", solver.plan()?);
+    solver.solve_with_report()
 }
 /// Main function of the crate
 pub fn run(config: Config) {
@@ -111,28 +504,69 @@ pub fn run(config: Config) {
         .build_global()
         .unwrap();
 
-    let reader = io::stdin().lock();
-    let d = DistanceMatrix::read_from_phylip(reader).unwrap_or_else(|err| {
+    let mut d = read_distance_matrix(&config).unwrap_or_else(|err| {
         eprintln!("{err}");
         process::exit(1);
     });
-
-    let d = match config.algo {
-        Algorithm::Naive => NeighborJoiningSolver::<Canonical>::default(d).solve(),
-        Algorithm::RapidNJ => {
-            NeighborJoiningSolver::<RapidBtrees>::build(d, config.chunk_size).solve()
-        }
-        Algorithm::Hybrid => {
-            let naive_steps = d.size() * config.naive_percentage / 100;
-            NeighborJoiningSolver::<Hybrid>::build(d, config.chunk_size, naive_steps).solve()
+    if let Some(policy) = config.validation {
+        let report = d.validate_with(policy).unwrap_or_else(|err| {
+            eprintln!("{err}");
+            process::exit(1);
+        });
+        if !report.is_valid() {
+            eprintln!("Warning: {report}");
         }
+    }
+    if let Some(path) = &config.convert {
+        File::create(path)
+            .map_err(Error::from)
+            .and_then(|file| d.write_binary(io::BufWriter::new(file), config.precision))
+            .unwrap_or_else(|err| {
+                eprintln!("{err}");
+                process::exit(1);
+            });
+        return;
+    }
+
+    let negative_branches = config.negative_branches;
+    let solution = match config.algo {
+        Algorithm::Naive => NeighborJoiningSolver::<Canonical>::default(d)
+            .set_precision(config.precision)
+            .set_negative_branches(negative_branches)
+            .solve_with_report(),
+        Algorithm::RapidNJ => NeighborJoiningSolver::<RapidBtrees>::build(d, config.chunk_size)
+            .set_row_storage(config.row_storage)
+            .set_precision(config.precision)
+            .set_negative_branches(negative_branches)
+            .solve_with_report(),
+        Algorithm::BioNJ => NeighborJoiningSolver::<BioNJ>::default(d)
+            .set_negative_branches(negative_branches)
+            .solve_with_report(),
+        Algorithm::Unweighted => NeighborJoiningSolver::<Unweighted>::default(d)
+            .set_negative_branches(negative_branches)
+            .solve_with_report(),
+        Algorithm::Clustering(linkage) => NeighborJoiningSolver::<Clustering>::build(d, linkage)
+            .set_negative_branches(negative_branches)
+            .solve_with_report(),
+        Algorithm::Hybrid => solve_hybrid(d, &config),
+        Algorithm::Auto => solve_auto(d, &config),
     };
-    let graph = d.unwrap_or_else(|err| {
+    let solution = solution.unwrap_or_else(|err| {
+        eprintln!("{err}");
+        process::exit(1);
+    });
+    if solution.adjusted_branches > 0 {
+        eprintln!(
+            "Adjusted {} negative branch lengths",
+            solution.adjusted_branches
+        );
+    }
+    let newick = speedytree::to_newick(&solution.tree).unwrap_or_else(|err| {
         eprintln!("{err}");
         process::exit(1);
     });
     io::stdout()
-        .write_all(speedytree::to_newick(&graph).as_bytes())
+        .write_all(newick.as_bytes())
         .unwrap_or_else(|err| {
             eprintln!("{err}");
             process::exit(1);
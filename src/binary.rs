@@ -0,0 +1,319 @@
+//! Binary distance matrix format, read through a memory map.
+//!
+//! Every number is little-endian. The file starts with a 32-byte header:
+//!
+//! | Offset | Size | Content                                              |
+//! |--------|------|------------------------------------------------------|
+//! | 0      | 8    | Magic bytes `SPDYTREE`                               |
+//! | 8      | 4    | Version of the format (1)                            |
+//! | 12     | 4    | Bytes per distance: 8 (f64) or 4 (f32)               |
+//! | 16     | 8    | Number of taxa n                                     |
+//! | 24     | 8    | Length in bytes of the names                         |
+//!
+//! It is followed by the names, every one ended by a newline, and by zeros up to a multiple of 8
+//! bytes. The payload is the packed lower triangle of the matrix: the distances
+//! d(i, 0), ..., d(i, i - 1) of every taxon i in turn, n (n - 1) / 2 values in total.
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+
+use memmap2::Mmap;
+use rayon::prelude::*;
+
+use crate::{
+    distances::Storage, float::Float, triangular::LowerTriangle, DistanceMatrix, Error, Precision,
+    Result,
+};
+
+const MAGIC: &[u8; 8] = b"SPDYTREE";
+const VERSION: u32 = 1;
+const HEADER_LEN: usize = 32;
+
+impl Precision {
+    /// Bytes taken by every distance
+    pub(crate) fn width(&self) -> usize {
+        match self {
+            Precision::Double => 8,
+            Precision::Single => 4,
+        }
+    }
+}
+
+/// Write the header, the names and the packed lower triangle given by `distances`
+fn write<W: Write>(
+    mut writer: W,
+    names: &[String],
+    distances: impl Iterator<Item = f64>,
+    precision: Precision,
+) -> Result<()> {
+    if let Some(name) = names.iter().find(|name| name.contains('\n')) {
+        return Err(Error::InvalidParameter(format!(
+            "taxon name {name:?} contains a newline"
+        )));
+    }
+    let names_len: usize = names.iter().map(|name| name.len() + 1).sum();
+    writer.write_all(MAGIC)?;
+    writer.write_all(&VERSION.to_le_bytes())?;
+    writer.write_all(&(precision.width() as u32).to_le_bytes())?;
+    writer.write_all(&(names.len() as u64).to_le_bytes())?;
+    writer.write_all(&(names_len as u64).to_le_bytes())?;
+    for name in names {
+        writer.write_all(name.as_bytes())?;
+        writer.write_all(b"\n")?;
+    }
+    writer.write_all(&vec![0; padding(names_len)])?;
+    for distance in distances {
+        match precision {
+            Precision::Double => writer.write_all(&distance.to_le_bytes())?,
+            Precision::Single => writer.write_all(&(distance as f32).to_le_bytes())?,
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+impl DistanceMatrix {
+    /// Map a binary distance matrix (see [`DistanceMatrix::write_binary`]). Only the header and the
+    /// names are read: the distances stay in the file until they are accessed, and nothing is parsed.
+    /// The solvers update the distances as they join taxa, so they still copy the whole matrix into
+    /// memory (in their precision) when they start.
+    /// The file must not be modified while the matrix is in use.
+    pub fn read_binary(path: impl AsRef<Path>) -> Result<DistanceMatrix> {
+        let (triangle, names) = MappedTriangle::open(path.as_ref())?;
+        Ok(DistanceMatrix::from_storage(
+            Storage::Mapped(triangle),
+            names,
+        ))
+    }
+    /// Write the matrix in the binary format: a header with the number of taxa and the precision,
+    /// the names and the packed lower triangle. Single precision halves the size of the file.
+    pub fn write_binary<W: Write>(&self, writer: W, precision: Precision) -> Result<()> {
+        write(writer, &self.names, self.lower_triangle(), precision)
+    }
+}
+
+/// Zeros needed after the names so the payload starts at a multiple of 8 bytes
+fn padding(names_len: usize) -> usize {
+    (8 - names_len % 8) % 8
+}
+
+/// Packed lower triangle stored in a memory-mapped binary file
+#[derive(Debug, Clone)]
+pub(crate) struct MappedTriangle {
+    map: Arc<Mmap>,
+    payload: usize,
+    n: usize,
+    precision: Precision,
+}
+
+impl MappedTriangle {
+    /// Map the file and read its header and names. The distances are only read when used.
+    pub fn open(path: &Path) -> Result<(Self, Vec<String>)> {
+        let file = File::open(path)?;
+        // Safety: the file is only read, and it must not be modified while it is mapped
+        let map = unsafe { Mmap::map(&file)? };
+        let header = map
+            .get(..HEADER_LEN)
+            .ok_or_else(|| Error::Format("the file is shorter than the header".to_string()))?;
+        if &header[..8] != MAGIC {
+            return Err(Error::Format(
+                "not a speedytree binary distance matrix".to_string(),
+            ));
+        }
+        let u32_at =
+            |offset: usize| u32::from_le_bytes(header[offset..offset + 4].try_into().unwrap());
+        let u64_at =
+            |offset: usize| u64::from_le_bytes(header[offset..offset + 8].try_into().unwrap());
+        let version = u32_at(8);
+        if version != VERSION {
+            return Err(Error::Format(format!("unsupported version {version}")));
+        }
+        let precision = match u32_at(12) {
+            8 => Precision::Double,
+            4 => Precision::Single,
+            width => {
+                return Err(Error::Format(format!(
+                    "unsupported distances of {width} bytes"
+                )))
+            }
+        };
+        let n = u64_at(16) as usize;
+        let names_len = u64_at(24) as usize;
+        let names = map
+            .get(HEADER_LEN..HEADER_LEN.saturating_add(names_len))
+            .ok_or_else(|| Error::Format("the names are truncated".to_string()))?;
+        let names = std::str::from_utf8(names)
+            .map_err(|_| Error::Format("the names are not valid UTF-8".to_string()))?;
+        let names: Vec<String> = names.split_terminator('\n').map(String::from).collect();
+        if names.len() != n {
+            return Err(Error::Format(format!(
+                "expected {n} names, found {}",
+                names.len()
+            )));
+        }
+        let payload = HEADER_LEN + names_len + padding(names_len);
+        let expected = LowerTriangle::<f64>::len(n)
+            .checked_mul(precision.width())
+            .and_then(|len| len.checked_add(payload));
+        if expected != Some(map.len()) {
+            return Err(Error::Format(format!(
+                "expected {} bytes for {n} taxa, found {}",
+                expected.map_or_else(|| "too many".to_string(), |len| len.to_string()),
+                map.len()
+            )));
+        }
+        let triangle = MappedTriangle {
+            map: Arc::new(map),
+            payload,
+            n,
+            precision,
+        };
+        Ok((triangle, names))
+    }
+    /// Number of taxa
+    pub fn size(&self) -> usize {
+        self.n
+    }
+    fn value(&self, index: usize) -> f64 {
+        let width = self.precision.width();
+        let bytes = &self.map[self.payload + index * width..][..width];
+        match self.precision {
+            Precision::Double => f64::from_le_bytes(bytes.try_into().unwrap()),
+            Precision::Single => f32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+        }
+    }
+    /// Distance between i and j (zero on the diagonal)
+    pub fn get(&self, i: usize, j: usize) -> f64 {
+        if i == j {
+            return 0.0;
+        }
+        let (i, j) = (i.max(j), i.min(j));
+        self.value(i * (i - 1) / 2 + j)
+    }
+    /// Copy the distances into memory, converted to T
+    pub fn to_triangle<T: Float>(&self) -> LowerTriangle<T> {
+        let width = self.precision.width();
+        let payload = &self.map[self.payload..];
+        let values = match self.precision {
+            Precision::Double => payload
+                .par_chunks_exact(width)
+                .map(|bytes| T::from_f64(f64::from_le_bytes(bytes.try_into().unwrap())))
+                .collect(),
+            Precision::Single => payload
+                .par_chunks_exact(width)
+                .map(|bytes| T::from_f64(f32::from_le_bytes(bytes.try_into().unwrap()) as f64))
+                .collect(),
+        };
+        LowerTriangle::from_values(values, self.n).expect("Valid payload length")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn example() -> DistanceMatrix {
+        let input = "5
+a	0	5	9	9	8
+b	5	0	10	10	9
+c	9	10	0	8	7
+d	9	10	8	0	3
+e	8	9	7	3	0.1
+"
+        .as_bytes();
+        DistanceMatrix::read_from_phylip(input).unwrap()
+    }
+
+    /// Temporary file, removed when dropped (even when the test fails)
+    struct TempFile(PathBuf);
+
+    impl AsRef<Path> for TempFile {
+        fn as_ref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn write_file(name: &str, d: &DistanceMatrix, precision: Precision) -> TempFile {
+        let path = std::env::temp_dir().join(format!("speedytree_{}_{name}", std::process::id()));
+        let file = File::create(&path).unwrap();
+        d.write_binary(std::io::BufWriter::new(file), precision)
+            .unwrap();
+        TempFile(path)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let d = example();
+        let path = write_file("double.bin", &d, Precision::Double);
+        // Header, 10 bytes of names padded to 16 and 10 distances
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 32 + 16 + 80);
+        let mapped = DistanceMatrix::read_binary(&path).unwrap();
+        assert_eq!(mapped.names, d.names);
+        assert_eq!(mapped.to_rows(), d.to_rows());
+        // The non-zero diagonal is not written
+        assert_eq!(mapped.get(4, 4), 0.0);
+        let path = write_file("single.bin", &d, Precision::Single);
+        let mapped = DistanceMatrix::read_binary(&path).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 32 + 16 + 40);
+        assert_eq!(mapped.to_rows(), d.to_rows());
+    }
+
+    #[test]
+    fn test_solve_mapped_matrix() {
+        use crate::{Canonical, NeighborJoiningSolver, RapidBtrees};
+        let d = example();
+        let path = write_file("solve.bin", &d, Precision::Double);
+        let mut mapped = DistanceMatrix::read_binary(&path).unwrap();
+        let expected = NeighborJoiningSolver::<Canonical>::default(d.clone())
+            .solve()
+            .unwrap();
+        let tree = NeighborJoiningSolver::<Canonical>::default(mapped.clone())
+            .solve()
+            .unwrap();
+        assert_eq!(
+            crate::to_newick(&tree).unwrap(),
+            crate::to_newick(&expected).unwrap()
+        );
+        let tree = NeighborJoiningSolver::<RapidBtrees>::default(mapped.clone())
+            .solve()
+            .unwrap();
+        assert_eq!(crate::robinson_foulds(&tree, &expected).unwrap(), 0);
+        // Modifying a mapped matrix copies it into memory and leaves the file untouched
+        mapped.set(0, 1, 6.0).unwrap();
+        assert_eq!(mapped.get(1, 0), 6.0);
+        let file = DistanceMatrix::read_binary(&path).unwrap();
+        assert_eq!(file.get(1, 0), 5.0);
+    }
+
+    #[test]
+    fn test_malformed_files() {
+        let d = example();
+        let path = write_file("malformed.bin", &d, Precision::Double);
+        let bytes = std::fs::read(&path).unwrap();
+        let cases = [
+            (bytes[..bytes.len() - 1].to_vec(), "expected 128 bytes"),
+            (bytes[..20].to_vec(), "shorter than the header"),
+            (b"NOTSPEEDYTREE".repeat(4), "not a speedytree"),
+        ];
+        for (content, message) in cases {
+            std::fs::write(&path, content).unwrap();
+            let err = DistanceMatrix::read_binary(&path).unwrap_err();
+            assert!(matches!(err, Error::Format(_)));
+            assert!(err.to_string().contains(message), "{err}");
+        }
+        let mut names = d.clone();
+        names.names[0] = "a\nb".to_string();
+        let err = names
+            .write_binary(Vec::new(), Precision::Double)
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidParameter(_)));
+    }
+}
@@ -0,0 +1,40 @@
+use crate::{
+    distances::DistanceMatrix,
+    naive_nj::{terminate_nj, PhyloTree, QMatrix},
+    Result, Tree,
+};
+
+/// BIONJ (Gascuel 1997). Neighbors are chosen as in the canonical algorithm, but the distances to the
+/// new node are a weighted average that minimizes its variance, estimated with a second matrix.
+pub fn bionj(dist: DistanceMatrix) -> Result<Tree> {
+    let mut t = PhyloTree::build(&dist.names);
+    // The variances start as the distances and follow the same layout
+    let mut v = QMatrix::<f64>::build(dist.clone());
+    let mut q = QMatrix::<f64>::build(dist);
+    while q.n_leaves() > 3 {
+        let (i, j) = q.find_neighbors();
+        let (dist_ui, dist_uj) = q.new_node_distances(i, j);
+        let lambda = lambda(&v, i, j);
+        let var_ij = v.distance(i, j);
+        t.merge_neighbors(i, j, dist_ui, dist_uj);
+        q.update_with(i, j, |dik, djk| {
+            lambda * (dik - dist_ui) + (1.0 - lambda) * (djk - dist_uj)
+        });
+        v.update_with(i, j, |vik, vjk| {
+            lambda * vik + (1.0 - lambda) * vjk - lambda * (1.0 - lambda) * var_ij
+        });
+    }
+    Ok(terminate_nj(t, q))
+}
+
+/// Weight of i in the distances to the new node, within [0, 1]
+fn lambda(v: &QMatrix, i: usize, j: usize) -> f64 {
+    let var_ij = v.distance(i, j);
+    if var_ij == 0.0 {
+        return 0.5;
+    }
+    // Sum over k != i, j of V(j, k) - V(i, k)
+    let diff = v.sum_col(j) - v.sum_col(i);
+    let n = v.n_leaves() as f64;
+    (0.5 + diff / (2.0 * (n - 2.0) * var_ij)).clamp(0.0, 1.0)
+}
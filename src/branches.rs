@@ -0,0 +1,161 @@
+use std::collections::BTreeMap;
+
+use crate::Tree;
+
+/// What to do with the negative branch lengths that neighbor joining produces on non-additive data
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NegativeBranches {
+    /// Leave them as they are
+    #[default]
+    Keep,
+    /// Set them to zero
+    Zero,
+    /// Set them to zero and add the difference to the longest sibling branch, so the distance
+    /// between the two siblings is kept (as QuickTree and RapidNJ). The sibling is clamped to zero
+    /// when the difference is larger than its length.
+    Transfer,
+}
+
+impl NegativeBranches {
+    /// Adjust the negative branches of a tree built by the solvers of this crate and return how many were adjusted.
+    /// Siblings are the edges that leave the same node, as the solvers add the edges from every new node
+    /// to the two nodes it merges (the last three edges of neighbor joining are siblings too).
+    pub fn apply(self, tree: &mut Tree) -> usize {
+        if self == NegativeBranches::Keep {
+            return 0;
+        }
+        let mut parents = BTreeMap::new();
+        for e in tree.edge_indices() {
+            let parent = tree.edge_endpoints(e).expect("Valid edge").0;
+            parents.entry(parent).or_insert_with(Vec::new).push(e);
+        }
+        let mut adjusted = 0;
+        for siblings in parents.values() {
+            // Both found from the original lengths, before any sibling is adjusted
+            let deficit: f64 = siblings
+                .iter()
+                .map(|e| tree[*e])
+                .filter(|weight| *weight < 0.0)
+                .sum();
+            let longest = siblings
+                .iter()
+                .copied()
+                .filter(|e| tree[*e] >= 0.0)
+                .max_by(|a, b| tree[*a].total_cmp(&tree[*b]));
+            for edge in siblings {
+                if tree[*edge] < 0.0 {
+                    tree[*edge] = 0.0;
+                    adjusted += 1;
+                }
+            }
+            if self == NegativeBranches::Transfer {
+                if let Some(longest) = longest {
+                    tree[longest] = (tree[longest] + deficit).max(0.0);
+                }
+            }
+        }
+        adjusted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DistanceMatrix, NeighborJoiningSolver, RapidBtrees};
+
+    fn non_additive() -> DistanceMatrix {
+        // Violates the triangle inequality, so c and d get negative branches
+        let input = "5
+a	0	9	1	9	5
+b	9	0	9	1	5
+c	1	9	0	1	2
+d	9	1	1	0	7
+e	5	5	2	7	0
+"
+        .as_bytes();
+        DistanceMatrix::read_from_phylip(input).unwrap()
+    }
+
+    fn negatives(tree: &Tree) -> usize {
+        tree.edge_weights().filter(|w| **w < 0.0).count()
+    }
+
+    #[test]
+    fn test_policies() {
+        let d = non_additive();
+        let tree = NeighborJoiningSolver::<RapidBtrees>::default(d.clone())
+            .solve()
+            .unwrap();
+        let n = negatives(&tree);
+        assert!(n > 0);
+        assert_eq!(NegativeBranches::Keep.apply(&mut tree.clone()), 0);
+
+        let mut zero = tree.clone();
+        assert_eq!(NegativeBranches::Zero.apply(&mut zero), n);
+        assert_eq!(negatives(&zero), 0);
+        let total = |t: &Tree| t.edge_weights().sum::<f64>();
+        assert!(total(&zero) > total(&tree));
+
+        // The total length is kept when moving the difference to the siblings
+        let mut transfer = tree.clone();
+        assert_eq!(NegativeBranches::Transfer.apply(&mut transfer), n);
+        assert_eq!(negatives(&transfer), 0);
+        assert!((total(&transfer) - total(&tree)).abs() < 1e-12);
+
+        let solved = NeighborJoiningSolver::<RapidBtrees>::default(d)
+            .set_negative_branches(NegativeBranches::Transfer)
+            .solve_with_report()
+            .unwrap();
+        assert_eq!(
+            crate::to_newick(&solved.tree).unwrap(),
+            crate::to_newick(&transfer).unwrap()
+        );
+        assert_eq!(solved.adjusted_branches, n);
+    }
+
+    #[test]
+    fn test_transfer_from_two_negative_siblings() {
+        // Siblings of u with lengths a, b and c
+        let siblings = |a: f64, b: f64, c: f64| {
+            let mut tree = Tree::default();
+            let u = tree.add_node(String::new());
+            for (name, weight) in [("a", a), ("b", b), ("c", c)] {
+                let leaf = tree.add_node(name.to_string());
+                tree.add_edge(u, leaf, weight);
+            }
+            tree
+        };
+        let mut tree = siblings(-1.0, -2.0, 5.0);
+        assert_eq!(NegativeBranches::Transfer.apply(&mut tree), 2);
+        assert_eq!(negatives(&tree), 0);
+        assert_eq!(
+            tree.edge_weights().copied().collect::<Vec<_>>(),
+            [0.0, 0.0, 2.0]
+        );
+        // A deficit longer than the longest sibling leaves it at zero
+        let mut tree = siblings(-3.0, 1.0, -4.0);
+        assert_eq!(NegativeBranches::Transfer.apply(&mut tree), 2);
+        assert_eq!(negatives(&tree), 0);
+        let mut tree = siblings(-3.0, -1.0, -4.0);
+        assert_eq!(NegativeBranches::Transfer.apply(&mut tree), 3);
+        assert_eq!(negatives(&tree), 0);
+    }
+
+    #[test]
+    fn test_transfer_between_interleaved_siblings() {
+        // The edges of u and v are added alternately
+        let mut tree = Tree::default();
+        let u = tree.add_node(String::new());
+        let v = tree.add_node(String::new());
+        for (parent, name, weight) in [(u, "a", -1.0), (v, "b", 4.0), (u, "c", 3.0), (v, "d", -2.0)]
+        {
+            let leaf = tree.add_node(name.to_string());
+            tree.add_edge(parent, leaf, weight);
+        }
+        assert_eq!(NegativeBranches::Transfer.apply(&mut tree), 2);
+        assert_eq!(
+            tree.edge_weights().copied().collect::<Vec<_>>(),
+            [0.0, 2.0, 2.0, 0.0]
+        );
+    }
+}
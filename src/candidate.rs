@@ -0,0 +1,54 @@
+use std::cmp::Ordering;
+
+use crate::float::Float;
+
+/// A pair of nodes to be joined and its q value.
+///
+/// Nodes are identified by their creation index: the leaves first (in the order of the distance matrix)
+/// and then the new nodes in the order they are merged. Candidates are ordered by q and then
+/// lexicographically by the pair of indexes, so a solver breaks ties in the same way whatever the
+/// number of threads or the chunk size. Different solvers may still break them differently, as
+/// they round the q values differently.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Candidate<T> {
+    pub q: T,
+    pub pair: (usize, usize),
+}
+
+impl<T: Float> Candidate<T> {
+    pub fn new(q: T, a: usize, b: usize) -> Self {
+        Candidate {
+            q,
+            pair: (a.min(b), a.max(b)),
+        }
+    }
+    /// Worst possible candidate, so that any other one is better
+    pub fn none() -> Self {
+        Candidate {
+            q: T::INFINITY,
+            pair: (usize::MAX, usize::MAX),
+        }
+    }
+    pub fn is_better_than(&self, other: &Self) -> bool {
+        self.q
+            .total_cmp(&other.q)
+            .then_with(|| self.pair.cmp(&other.pair))
+            == Ordering::Less
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ties_are_broken_by_index() {
+        let a = Candidate::new(-10.0f64, 3, 1);
+        assert_eq!(a.pair, (1, 3));
+        assert!(a.is_better_than(&Candidate::new(-10.0, 2, 3)));
+        assert!(Candidate::new(-10.0, 0, 5).is_better_than(&a));
+        assert!(Candidate::new(-11.0, 7, 8).is_better_than(&a));
+        assert!(!a.is_better_than(&a));
+        assert!(a.is_better_than(&Candidate::none()));
+    }
+}
@@ -0,0 +1,45 @@
+use crate::{
+    distances::DistanceMatrix,
+    naive_nj::{merge_layout, PhyloTree, QMatrix},
+    Result, Tree,
+};
+
+/// How the distance between two clusters is computed from the distances between their members
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Linkage {
+    /// Average of the distances between the members (unweighted pair group method with arithmetic mean)
+    #[default]
+    Upgma,
+    /// Average of the distances of the two merged clusters (weighted pair group method with arithmetic mean)
+    Wpgma,
+    /// Smallest distance between the members
+    Single,
+    /// Largest distance between the members
+    Complete,
+}
+
+/// Agglomerative clustering. The two closest clusters are merged at half of their distance,
+/// so the result is a rooted ultrametric tree (the root is the only internal node with two edges).
+pub fn agglomerative_clustering(dist: DistanceMatrix, linkage: Linkage) -> Result<Tree> {
+    let mut t = PhyloTree::build(&dist.names);
+    // Number of taxa and height of every cluster, following the layout of the QMatrix
+    let mut sizes = vec![1; dist.size()];
+    let mut heights = vec![0.0; dist.size()];
+    let mut q = QMatrix::<f64>::build(dist);
+    while q.n_leaves() > 1 {
+        let (i, j) = q.find_closest();
+        let height = q.distance(i, j) / 2.0;
+        t.merge_neighbors(i, j, height - heights[i], height - heights[j]);
+        let (size_i, size_j) = (sizes[i] as f64, sizes[j] as f64);
+        q.update_with(i, j, |dik, djk| match linkage {
+            Linkage::Upgma => (size_i * dik + size_j * djk) / (size_i + size_j),
+            Linkage::Wpgma => (dik + djk) / 2.0,
+            Linkage::Single => dik.min(djk),
+            Linkage::Complete => dik.max(djk),
+        });
+        let merged = sizes[i] + sizes[j];
+        merge_layout(&mut sizes, i, j, merged);
+        merge_layout(&mut heights, i, j, height);
+    }
+    Ok(t.tree)
+}
@@ -0,0 +1,75 @@
+mod algorithm;
+pub use algorithm::{agglomerative_clustering, Linkage};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distances::DistanceMatrix;
+    use petgraph::algo::dijkstra;
+
+    fn example() -> DistanceMatrix {
+        // Wikipedia example of UPGMA (5S ribosomal RNA of five bacteria)
+        DistanceMatrix::build(
+            vec![
+                vec![0.0, 17.0, 21.0, 31.0, 23.0],
+                vec![17.0, 0.0, 30.0, 34.0, 21.0],
+                vec![21.0, 30.0, 0.0, 28.0, 39.0],
+                vec![31.0, 34.0, 28.0, 0.0, 43.0],
+                vec![23.0, 21.0, 39.0, 43.0, 0.0],
+            ],
+            vec![
+                "a".to_string(),
+                "b".to_string(),
+                "c".to_string(),
+                "d".to_string(),
+                "e".to_string(),
+            ],
+        )
+        .unwrap()
+    }
+
+    /// Distance from the root to every leaf
+    fn leaf_heights(tree: &crate::Tree) -> Vec<f64> {
+        let root = tree
+            .node_indices()
+            .find(|node| tree[*node].is_empty() && tree.neighbors(*node).count() == 2)
+            .unwrap();
+        let distances = dijkstra(tree, root, None, |e| *e.weight());
+        tree.node_indices()
+            .filter(|node| !tree[*node].is_empty())
+            .map(|node| distances[&node])
+            .collect()
+    }
+
+    #[test]
+    fn test_upgma_wikipedia() {
+        let tree = agglomerative_clustering(example(), Linkage::Upgma).unwrap();
+        // Rooted binary tree: 5 leaves, 4 internal nodes
+        assert_eq!(tree.node_count(), 9);
+        assert_eq!(tree.edge_count(), 8);
+        for height in leaf_heights(&tree) {
+            assert!((height - 16.5).abs() < 1e-12);
+        }
+        assert_eq!(
+            crate::to_newick(&tree).unwrap(),
+            "((e:11.0,(a:8.5,b:8.5):2.5):5.5,(d:14.0,c:14.0):2.5);"
+        );
+    }
+
+    #[test]
+    fn test_linkages_are_ultrametric() {
+        // Heights of the root of the Wikipedia example
+        let expected = [
+            (Linkage::Upgma, 16.5),
+            (Linkage::Wpgma, 17.5),
+            (Linkage::Single, 14.0),
+            (Linkage::Complete, 21.5),
+        ];
+        for (linkage, root_height) in expected {
+            let tree = agglomerative_clustering(example(), linkage).unwrap();
+            for height in leaf_heights(&tree) {
+                assert!((height - root_height).abs() < 1e-12);
+            }
+        }
+    }
+}
@@ -1,48 +1,461 @@
-use crate::ResultBox;
+use crate::{binary::MappedTriangle, float::Float, triangular::LowerTriangle, Error, Result};
+use std::collections::VecDeque;
 use std::io::{self};
-/// Distance matrix data structure
+/// Distance matrix data structure.
+///
+/// The matrix is symmetric with a zero diagonal, so only its lower triangle is stored, packed in a
+/// single vector (or mapped from a binary file, see [`DistanceMatrix::read_binary`]). Distances are
+/// read and written with [`DistanceMatrix::get`] and [`DistanceMatrix::set`].
 #[derive(Debug, Clone)]
 pub struct DistanceMatrix {
-    /// Distance matrix
-    pub matrix: Vec<Vec<f64>>,
+    /// Lower triangle of the distance matrix
+    distances: Storage,
     /// Names of the taxa
     pub names: Vec<String>,
+    /// Entries of the square matrix that the triangle cannot hold (see [`DistanceMatrix::validate`])
+    pub(crate) unpacked: Unpacked,
+}
+
+/// Entries of a square matrix that its lower triangle cannot hold, kept until they are reported
+/// and repaired by [`DistanceMatrix::validate_with`]. The solvers only read the lower triangle.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Unpacked {
+    /// Pairs (i, j), with i < j, and their d(i, j), where the triangle holds a different d(j, i)
+    pub upper: Vec<(usize, usize, f64)>,
+    /// Taxa and their non-zero distance to themselves
+    pub diagonal: Vec<(usize, f64)>,
+}
+
+/// Where the lower triangle of a distance matrix is stored
+#[derive(Debug, Clone)]
+pub(crate) enum Storage {
+    /// In memory, as f64
+    Memory(LowerTriangle<f64>),
+    /// Mapped from a binary file, in the precision of the file
+    Mapped(MappedTriangle),
+}
+
+/// Layout of the rows of a [PHYLIP](https://phylipweb.github.io/phylip/) distance matrix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PhylipLayout {
+    /// Every row contains the n distances
+    Square,
+    /// Row i contains the i distances to the previous taxa (as written by `dnadist`/`protdist`)
+    Lower,
+    /// Row i contains the n - i - 1 distances to the following taxa
+    Upper,
+}
+
+impl PhylipLayout {
+    /// Number of distances expected in the i-th row of a matrix with n taxa
+    fn row_len(&self, n: usize, i: usize) -> usize {
+        match self {
+            PhylipLayout::Square => n,
+            PhylipLayout::Lower => i,
+            PhylipLayout::Upper => n - i - 1,
+        }
+    }
+}
+
+/// How taxon names are read from a [PHYLIP](https://phylipweb.github.io/phylip/) distance matrix
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PhylipNames {
+    /// The name is the first whitespace-separated word of the row
+    #[default]
+    Relaxed,
+    /// The name is the first 10 characters of the row. It may contain spaces or be glued to the first distance.
+    Strict,
+}
+
+impl PhylipNames {
+    /// Split a row into the taxon name and the remaining distances
+    pub(crate) fn split<'a>(&self, line: &'a str) -> (&'a str, &'a str) {
+        match self {
+            PhylipNames::Relaxed => {
+                let line = line.trim_start();
+                let end = line.find(char::is_whitespace).unwrap_or(line.len());
+                line.split_at(end)
+            }
+            PhylipNames::Strict => {
+                let end = line.char_indices().nth(10).map_or(line.len(), |(i, _)| i);
+                let (name, rest) = line.split_at(end);
+                (name.trim(), rest)
+            }
+        }
+    }
+}
+
+/// Non-empty lines of a PHYLIP file together with their (1-based) line number
+struct PhylipLines<R: io::BufRead> {
+    lines: std::iter::Enumerate<io::Lines<R>>,
+    /// Non-empty lines read ahead by `peek_nth`
+    ahead: VecDeque<(usize, io::Result<String>)>,
+    last: usize,
+}
+
+impl<R: io::BufRead> PhylipLines<R> {
+    fn new(reader: R) -> Self {
+        PhylipLines {
+            lines: reader.lines().enumerate(),
+            ahead: VecDeque::new(),
+            last: 0,
+        }
+    }
+    /// Skip blank lines and return a reference to the n-th next non-empty line (from 0)
+    fn peek_nth(&mut self, n: usize) -> Option<&str> {
+        while self.ahead.len() <= n {
+            match self.lines.next() {
+                Some((_, Ok(line))) if line.trim().is_empty() => {}
+                Some((index, line)) => self.ahead.push_back((index + 1, line)),
+                None => break,
+            }
+        }
+        match self.ahead.get(n) {
+            Some((_, Ok(line))) => Some(line.as_str()),
+            _ => None,
+        }
+    }
+    /// Skip blank lines and return a reference to the next non-empty line
+    fn peek(&mut self) -> Option<&str> {
+        self.peek_nth(0)
+    }
+    /// Next non-empty line and its line number
+    fn next(&mut self) -> Result<Option<(usize, String)>> {
+        self.peek();
+        match self.ahead.pop_front() {
+            Some((number, line)) => {
+                self.last = number;
+                Ok(Some((number, line?)))
+            }
+            None => Ok(None),
+        }
+    }
+    /// Next non-empty line, failing if the input ended before
+    fn expect(&mut self, what: &str) -> Result<(usize, String)> {
+        self.next()?.ok_or_else(|| {
+            Error::parse(
+                self.last + 1,
+                format!("unexpected end of input, expected {what}"),
+            )
+        })
+    }
+}
+
+/// Parse every whitespace-separated word of `text` as a distance
+fn parse_distances(text: &str, line: usize, row: &mut Vec<f64>) -> Result<()> {
+    for word in text.split_whitespace() {
+        let value = word
+            .parse::<f64>()
+            .map_err(|_| Error::parse(line, format!("cannot parse '{word}' as a distance")))?;
+        row.push(value);
+    }
+    Ok(())
+}
+
+/// Packs the rows of a matrix into its lower triangle, one row at a time
+struct Packer {
+    distances: LowerTriangle<f64>,
+    layout: PhylipLayout,
+    next: usize,
+    unpacked: Unpacked,
+}
+
+impl Packer {
+    fn new(n: usize, layout: PhylipLayout) -> Self {
+        Packer {
+            distances: LowerTriangle::zeros(n),
+            layout,
+            next: 0,
+            unpacked: Unpacked::default(),
+        }
+    }
+    /// Pack the next row, whose length must follow the layout. The lower triangle of a square matrix
+    /// is stored, and the different upper values and the non-zero diagonal are kept aside.
+    fn push(&mut self, row: &[f64]) {
+        let i = self.next;
+        self.next += 1;
+        match self.layout {
+            PhylipLayout::Lower => self.distances.row_mut(i).copy_from_slice(row),
+            PhylipLayout::Upper => {
+                for (offset, value) in row.iter().enumerate() {
+                    self.distances.set(i + offset + 1, i, *value);
+                }
+            }
+            PhylipLayout::Square => {
+                if row[i] != 0.0 {
+                    self.unpacked.diagonal.push((i, row[i]));
+                }
+                // d(j, i) was already stored by the rows before, and is replaced by d(i, j)
+                for (j, value) in row[..i].iter().enumerate() {
+                    let upper = self.distances.get(i, j);
+                    if upper != *value {
+                        if upper.is_finite() && value.is_finite() {
+                            self.unpacked.upper.push((j, i, upper));
+                            self.distances.set(i, j, *value);
+                        } else {
+                            // Whichever is NaN or infinite is kept, to be reported
+                            self.distances.set(i, j, (upper + value) / 2.0);
+                        }
+                    }
+                }
+                for (j, value) in row.iter().enumerate().skip(i + 1) {
+                    self.distances.set(j, i, *value);
+                }
+            }
+        }
+    }
+    fn finish(mut self) -> (LowerTriangle<f64>, Unpacked) {
+        self.unpacked
+            .upper
+            .sort_unstable_by_key(|&(i, j, _)| (i, j));
+        (self.distances, self.unpacked)
+    }
+}
+
+/// Whether the line is a single distance. When the first row already has n - 1 distances, such a
+/// line ends a wrapped square row, as the next row of an upper-triangular matrix has a name and
+/// at least one distance, or, with 2 taxa, is the last line.
+fn is_single_distance(line: &str) -> bool {
+    let mut words = line.split_whitespace();
+    words.next().is_some_and(|word| word.parse::<f64>().is_ok()) && words.next().is_none()
 }
 
 /// Distance matrix from a [PHYLIP](https://phylipweb.github.io/phylip/) file
 impl DistanceMatrix {
-    pub fn read_from_phylip<R>(mut reader: R) -> ResultBox<DistanceMatrix>
+    /// Read a square, lower-triangular or upper-triangular PHYLIP distance matrix with relaxed names.
+    /// Square matrices are packed into their lower triangle as they are read.
+    pub fn read_from_phylip<R>(reader: R) -> Result<DistanceMatrix>
+    where
+        R: io::BufRead,
+    {
+        Self::read_from_phylip_with(reader, PhylipNames::Relaxed)
+    }
+    /// Read a PHYLIP distance matrix, choosing how taxon names are parsed.
+    /// Rows may be wrapped over several lines (as `dnadist` does for long rows).
+    pub fn read_from_phylip_with<R>(reader: R, names_format: PhylipNames) -> Result<DistanceMatrix>
     where
         R: io::BufRead,
     {
-        let mut line = String::new();
-        reader.read_line(&mut line)?;
-        let n = line.trim().parse::<usize>()?;
-        // Read the next n lines to get the names of the sequences (first word), and parse the vector
+        let mut lines = PhylipLines::new(reader);
+        let (line_number, line) = lines.expect("the number of taxa")?;
+        let n = line
+            .split_whitespace()
+            .next()
+            .and_then(|word| word.parse::<usize>().ok())
+            .ok_or_else(|| Error::parse(line_number, "expected the number of taxa"))?;
+        let mut layout = None;
         let mut names = Vec::with_capacity(n);
-        let mut matrix = Vec::with_capacity(n);
-        for _ in 0..n {
-            line.clear();
-            reader.read_line(&mut line)?;
-            let mut words = line.split_whitespace();
-            names.push(words.next().expect("Valid Phylip format").to_string());
-            matrix.push(
-                words
-                    .map(|s| s.parse::<f64>().expect("Valid Phylip format"))
-                    .collect::<Vec<f64>>(),
-            );
-        }
-        Ok(DistanceMatrix { matrix, names })
+        let mut packer = None;
+        for i in 0..n {
+            let (line_number, line) = lines.expect(&format!("taxon {} of {}", i + 1, n))?;
+            let (name, rest) = names_format.split(&line);
+            if name.is_empty() {
+                return Err(Error::parse(line_number, "missing taxon name"));
+            }
+            let mut row = Vec::new();
+            parse_distances(rest, line_number, &mut row)?;
+            let layout = match layout {
+                Some(layout) => layout,
+                None => {
+                    // The first row tells the layout apart: it is empty when lower-triangular,
+                    // and otherwise wrapped up to the n - 1 distances of an upper-triangular row
+                    if !row.is_empty() {
+                        while row.len() + 1 < n
+                            || (row.len() + 1 == n
+                                && lines.peek().is_some_and(is_single_distance)
+                                && (n > 2 || lines.peek_nth(1).is_some()))
+                        {
+                            let (number, line) = lines.expect("distances")?;
+                            parse_distances(&line, number, &mut row)?;
+                        }
+                    }
+                    *layout.insert(match row.len() {
+                        0 => PhylipLayout::Lower,
+                        len if len == n => PhylipLayout::Square,
+                        len if len + 1 == n => PhylipLayout::Upper,
+                        len => {
+                            return Err(Error::parse(
+                                line_number,
+                                format!(
+                                    "expected {n} or {} distances for taxon '{name}', found {len}",
+                                    n - 1
+                                ),
+                            ))
+                        }
+                    })
+                }
+            };
+            let expected = layout.row_len(n, i);
+            while row.len() < expected {
+                let (number, line) = lines.expect(&format!("distances for taxon '{name}'"))?;
+                parse_distances(&line, number, &mut row)?;
+            }
+            if row.len() != expected {
+                return Err(Error::parse(
+                    line_number,
+                    format!(
+                        "expected {expected} distances for taxon '{name}', found {}",
+                        row.len()
+                    ),
+                ));
+            }
+            names.push(name.to_string());
+            packer
+                .get_or_insert_with(|| Packer::new(n, layout))
+                .push(&row);
+        }
+        if let Some((line_number, _)) = lines.next()? {
+            return Err(Error::parse(
+                line_number,
+                "unexpected data after the last taxon",
+            ));
+        }
+        let packer = packer.unwrap_or_else(|| Packer::new(n, PhylipLayout::Square));
+        let (distances, unpacked) = packer.finish();
+        Ok(DistanceMatrix {
+            distances: Storage::Memory(distances),
+            names,
+            unpacked,
+        })
+    }
+    /// Distance matrix from its storage, with nothing to report
+    pub(crate) fn from_storage(distances: Storage, names: Vec<String>) -> DistanceMatrix {
+        DistanceMatrix {
+            distances,
+            names,
+            unpacked: Unpacked::default(),
+        }
     }
     /// Size of the distance matrix
     pub fn size(&self) -> usize {
-        self.matrix.len()
+        match &self.distances {
+            Storage::Memory(triangle) => triangle.size(),
+            Storage::Mapped(triangle) => triangle.size(),
+        }
+    }
+    /// Build a distance matrix from its square rows and the names of the taxa.
+    /// The lower triangle is stored. Asymmetric pairs and a non-zero diagonal are reported by
+    /// [`DistanceMatrix::validate`], and asymmetric pairs are averaged by
+    /// [`DistanceMatrix::validate_with`] when the policy symmetrizes.
+    pub fn build(matrix: Vec<Vec<f64>>, names: Vec<String>) -> Result<DistanceMatrix> {
+        let n = matrix.len();
+        if n != names.len() {
+            return Err(Error::Validation(
+                "matrix and names have different lengths".to_string(),
+            ));
+        }
+        if let Some((i, row)) = matrix.iter().enumerate().find(|(_, row)| row.len() != n) {
+            return Err(Error::Validation(format!(
+                "row {i} has {} distances, expected {n}",
+                row.len()
+            )));
+        }
+        let mut packer = Packer::new(n, PhylipLayout::Square);
+        for row in matrix {
+            packer.push(&row);
+        }
+        let (distances, unpacked) = packer.finish();
+        Ok(DistanceMatrix {
+            distances: Storage::Memory(distances),
+            names,
+            unpacked,
+        })
+    }
+    /// Build a distance matrix from its packed lower triangle, where the distances
+    /// d(i, 0), ..., d(i, i - 1) of every taxon i follow each other
+    pub fn from_lower_triangle(distances: Vec<f64>, names: Vec<String>) -> Result<DistanceMatrix> {
+        let n = names.len();
+        let expected = LowerTriangle::<f64>::len(n);
+        let found = distances.len();
+        let distances = LowerTriangle::from_values(distances, n).ok_or_else(|| {
+            Error::Validation(format!(
+                "expected {expected} distances for {n} taxa, found {found}"
+            ))
+        })?;
+        Ok(Self::from_storage(Storage::Memory(distances), names))
+    }
+    /// Distance between the taxa i and j
+    pub fn get(&self, i: usize, j: usize) -> f64 {
+        match &self.distances {
+            Storage::Memory(triangle) => triangle.get(i, j),
+            Storage::Mapped(triangle) => triangle.get(i, j),
+        }
+    }
+    /// Set the distance between the different taxa i and j (in both directions).
+    /// A mapped matrix is copied into memory the first time it is modified.
+    pub fn set(&mut self, i: usize, j: usize, distance: f64) -> Result<()> {
+        let n = self.size();
+        if i >= n || j >= n {
+            return Err(Error::InvalidParameter(format!(
+                "taxa {i} and {j} are not both in a matrix of {n} taxa"
+            )));
+        }
+        if i == j {
+            return Err(Error::InvalidParameter(
+                "the diagonal of a distance matrix is always zero".to_string(),
+            ));
+        }
+        if let Storage::Mapped(triangle) = &self.distances {
+            self.distances = Storage::Memory(triangle.to_triangle());
+        }
+        if let Storage::Memory(triangle) = &mut self.distances {
+            triangle.set(i, j, distance);
+        }
+        let pair = (i.min(j), i.max(j));
+        self.unpacked.upper.retain(|&(a, b, _)| (a, b) != pair);
+        Ok(())
+    }
+    /// Every distance of the taxon i, in the order of the taxa
+    pub fn row(&self, i: usize) -> impl Iterator<Item = f64> + '_ {
+        (0..self.size()).map(move |j| self.get(i, j))
+    }
+    /// Packed lower triangle: the distances d(i, 0), ..., d(i, i - 1) of every taxon i in turn
+    pub fn lower_triangle(&self) -> impl Iterator<Item = f64> + '_ {
+        (1..self.size()).flat_map(move |i| (0..i).map(move |j| self.get(i, j)))
+    }
+    /// Expand the matrix into its square rows
+    pub fn to_rows(&self) -> Vec<Vec<f64>> {
+        (0..self.size()).map(|i| self.row(i).collect()).collect()
+    }
+    /// Square rows of the matrix, which were its public `matrix` field before it was packed
+    #[deprecated(
+        note = "the matrix is stored as its packed lower triangle, use `to_rows`, `row` or `get`"
+    )]
+    pub fn matrix(&self) -> Vec<Vec<f64>> {
+        self.to_rows()
+    }
+    /// Take the packed lower triangle, converted to the precision of a solver.
+    /// It is converted in place when it is in memory and T is f64.
+    pub(crate) fn into_triangle<T: Float>(self) -> LowerTriangle<T> {
+        match self.distances {
+            Storage::Memory(triangle) => triangle.map(T::from_f64),
+            Storage::Mapped(triangle) => triangle.to_triangle(),
+        }
     }
-    pub fn build(matrix: Vec<Vec<f64>>, names: Vec<String>) -> ResultBox<DistanceMatrix> {
-        if matrix.len() != names.len() {
-            return Err("Matrix and names have different lengths".into());
+    /// Check the minimum requirements of every solver: at least 3 taxa, one name each and finite distances
+    pub(crate) fn check_solvable(&self) -> Result<()> {
+        let n = self.size();
+        if n < 3 {
+            return Err(Error::Validation(format!(
+                "at least 3 taxa are needed, found {n}"
+            )));
         }
-        Ok(DistanceMatrix { matrix, names })
+        if self.names.len() != n {
+            return Err(Error::Validation(
+                "matrix and names have different lengths".to_string(),
+            ));
+        }
+        for i in 0..n {
+            if let Some(j) = (0..i).find(|j| !self.get(i, *j).is_finite()) {
+                return Err(Error::Numerical(format!(
+                    "distance between '{}' and '{}' is {}",
+                    self.names[i],
+                    self.names[j],
+                    self.get(i, j)
+                )));
+            }
+        }
+        Ok(())
     }
 }
 
@@ -65,7 +478,7 @@ D 9.0 10.0 8.0 0.0
         let distance_matrix = DistanceMatrix::read_from_phylip::<&[u8]>(input).unwrap();
         // check result
         assert_eq!(
-            distance_matrix.matrix,
+            distance_matrix.to_rows(),
             vec![
                 vec![0.0, 5.0, 9.0, 9.0],
                 vec![5.0, 0.0, 10.0, 10.0],
@@ -83,4 +496,236 @@ D 9.0 10.0 8.0 0.0
             ]
         );
     }
+
+    #[test]
+    fn test_lower_triangular() {
+        let input = "4
+A
+B 5.0
+C 9.0 10.0
+D 9.0 10.0 8.0
+"
+        .as_bytes();
+        let distance_matrix = DistanceMatrix::read_from_phylip::<&[u8]>(input).unwrap();
+        assert_eq!(
+            distance_matrix.to_rows(),
+            vec![
+                vec![0.0, 5.0, 9.0, 9.0],
+                vec![5.0, 0.0, 10.0, 10.0],
+                vec![9.0, 10.0, 0.0, 8.0],
+                vec![9.0, 10.0, 8.0, 0.0],
+            ]
+        );
+        assert_eq!(distance_matrix.names, vec!["A", "B", "C", "D"]);
+    }
+
+    #[test]
+    fn test_upper_triangular() {
+        let input = "4
+A 5.0 9.0 9.0
+B 10.0 10.0
+C 8.0
+D
+"
+        .as_bytes();
+        let distance_matrix = DistanceMatrix::read_from_phylip::<&[u8]>(input).unwrap();
+        assert_eq!(
+            distance_matrix.to_rows(),
+            vec![
+                vec![0.0, 5.0, 9.0, 9.0],
+                vec![5.0, 0.0, 10.0, 10.0],
+                vec![9.0, 10.0, 0.0, 8.0],
+                vec![9.0, 10.0, 8.0, 0.0],
+            ]
+        );
+        assert_eq!(distance_matrix.names, vec!["A", "B", "C", "D"]);
+    }
+
+    #[test]
+    fn test_numeric_names() {
+        let expected = vec![
+            vec![0.0, 5.0, 9.0, 9.0],
+            vec![5.0, 0.0, 10.0, 10.0],
+            vec![9.0, 10.0, 0.0, 8.0],
+            vec![9.0, 10.0, 8.0, 0.0],
+        ];
+        for input in [
+            "4\n1 5 9 9\n2 10 10\n3 8\n4\n",
+            "4\n1\n2 5\n3 9 10\n4 9 10 8\n",
+            // The first row of a square matrix, wrapped after n - 1 distances
+            "4\n1 0 5 9\n  9\n2 5 0 10 10\n3 9 10 0 8\n4 9 10 8 0\n",
+        ] {
+            let distance_matrix = DistanceMatrix::read_from_phylip(input.as_bytes()).unwrap();
+            assert_eq!(distance_matrix.to_rows(), expected, "{input}");
+            assert_eq!(distance_matrix.names, vec!["1", "2", "3", "4"]);
+        }
+    }
+
+    #[test]
+    fn test_two_taxa() {
+        let expected = vec![vec![0.0, 5.0], vec![5.0, 0.0]];
+        for input in [
+            // A square matrix whose first row is wrapped
+            "2\nA 0.0\n 5.0\nB 5.0 0.0\n",
+            "2\nA 5.0\nB\n",
+            "2\nA\nB 5.0\n",
+            "2\n1 5\n2\n",
+            "2\n1 0\n5\n2 5 0\n",
+        ] {
+            let distance_matrix = DistanceMatrix::read_from_phylip(input.as_bytes()).unwrap();
+            assert_eq!(distance_matrix.to_rows(), expected, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_trailing_data() {
+        let input = "3\na 0 1 2\nb 1 0 3\nc 2 3 0\n\n";
+        assert!(DistanceMatrix::read_from_phylip(input.as_bytes()).is_ok());
+        let input = "3\na 0 1 2\nb 1 0 3\nc 2 3 0\nGARBAGE 9 9 9\n";
+        assert!(matches!(
+            DistanceMatrix::read_from_phylip(input.as_bytes()),
+            Err(Error::Parse { line: 5, .. })
+        ));
+    }
+
+    #[test]
+    fn test_ragged_rows() {
+        let input = "3
+A 0.0 5.0
+B 5.0 0.0 1.0
+C 9.0 10.0 0.0
+"
+        .as_bytes();
+        assert!(DistanceMatrix::read_from_phylip::<&[u8]>(input).is_err());
+    }
+
+    #[test]
+    fn test_wrapped_rows() {
+        let input = "4
+A 0.0 5.0
+  9.0 9.0
+B 5.0 0.0 10.0
+  10.0
+
+C 9.0 10.0 0.0 8.0
+D 9.0
+  10.0 8.0 0.0
+"
+        .as_bytes();
+        let distance_matrix = DistanceMatrix::read_from_phylip::<&[u8]>(input).unwrap();
+        assert_eq!(distance_matrix.to_rows()[0], vec![0.0, 5.0, 9.0, 9.0]);
+        assert_eq!(distance_matrix.to_rows()[3], vec![9.0, 10.0, 8.0, 0.0]);
+        assert_eq!(distance_matrix.names, vec!["A", "B", "C", "D"]);
+    }
+
+    #[test]
+    fn test_wrapped_lower_triangular() {
+        let input = "4
+A
+B 5.0
+C 9.0
+  10.0
+D 9.0 10.0
+  8.0
+"
+        .as_bytes();
+        let distance_matrix = DistanceMatrix::read_from_phylip::<&[u8]>(input).unwrap();
+        assert_eq!(distance_matrix.to_rows()[2], vec![9.0, 10.0, 0.0, 8.0]);
+        assert_eq!(distance_matrix.to_rows()[3], vec![9.0, 10.0, 8.0, 0.0]);
+    }
+
+    #[test]
+    fn test_strict_names() {
+        let input = "3
+Homo sapie0.0 1.0 2.0
+Pan trogl 1.0 0.0 3.0
+Gorilla   2.0 3.0 0.0
+"
+        .as_bytes();
+        let distance_matrix =
+            DistanceMatrix::read_from_phylip_with::<&[u8]>(input, PhylipNames::Strict).unwrap();
+        assert_eq!(
+            distance_matrix.names,
+            vec!["Homo sapie", "Pan trogl", "Gorilla"]
+        );
+        assert_eq!(distance_matrix.to_rows()[0], vec![0.0, 1.0, 2.0]);
+    }
+
+    #[test]
+    fn test_errors_report_line_numbers() {
+        let input = "3
+A 0.0 1.0 2.0
+B 1.0 zero 3.0
+C 2.0 3.0 0.0
+"
+        .as_bytes();
+        let err = DistanceMatrix::read_from_phylip::<&[u8]>(input).unwrap_err();
+        assert_eq!(err.to_string(), "line 3: cannot parse 'zero' as a distance");
+        let input = "3
+A 0.0 1.0 2.0
+B 1.0 0.0 3.0
+"
+        .as_bytes();
+        let err = DistanceMatrix::read_from_phylip::<&[u8]>(input).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 4: unexpected end of input, expected taxon 3 of 3"
+        );
+    }
+
+    #[test]
+    fn test_check_solvable() {
+        let names = vec!["A".to_string(), "B".to_string(), "C".to_string()];
+        let d = DistanceMatrix::build(
+            vec![
+                vec![0.0, 1.0, f64::NAN],
+                vec![1.0, 0.0, 2.0],
+                vec![f64::NAN, 2.0, 0.0],
+            ],
+            names.clone(),
+        )
+        .unwrap();
+        assert!(matches!(d.check_solvable(), Err(Error::Numerical(_))));
+        let d = DistanceMatrix::build(vec![vec![0.0, 1.0], vec![1.0, 0.0]], names[..2].to_vec())
+            .unwrap();
+        assert!(matches!(d.check_solvable(), Err(Error::Validation(_))));
+    }
+
+    #[test]
+    fn test_packed_lower_triangle() {
+        let names: Vec<String> = ["A", "B", "C"].iter().map(|s| s.to_string()).collect();
+        let mut d = DistanceMatrix::build(
+            vec![
+                vec![0.0, 4.0, 9.0],
+                vec![6.0, 0.0, 10.0],
+                vec![9.0, 10.0, 1.0],
+            ],
+            names.clone(),
+        )
+        .unwrap();
+        // The lower triangle is stored, and the upper value and the diagonal are kept aside
+        assert_eq!(d.lower_triangle().collect::<Vec<_>>(), vec![6.0, 9.0, 10.0]);
+        assert_eq!(d.unpacked.upper, vec![(0, 1, 4.0)]);
+        assert_eq!(d.unpacked.diagonal, vec![(2, 1.0)]);
+        assert_eq!(d.get(1, 0), d.get(0, 1));
+        assert_eq!(d.get(2, 2), 0.0);
+        d.set(2, 0, 3.0).unwrap();
+        assert_eq!(d.row(0).collect::<Vec<_>>(), vec![0.0, 6.0, 3.0]);
+        for (i, j) in [(1, 1), (0, 3), (3, 0)] {
+            assert!(matches!(d.set(i, j, 1.0), Err(Error::InvalidParameter(_))));
+        }
+        assert_eq!(d.row(0).collect::<Vec<_>>(), vec![0.0, 6.0, 3.0]);
+        // Setting an asymmetric pair makes it symmetric
+        d.set(0, 1, 5.0).unwrap();
+        assert!(d.unpacked.upper.is_empty());
+        let e = DistanceMatrix::from_lower_triangle(vec![5.0, 3.0, 10.0], names.clone()).unwrap();
+        assert_eq!(e.to_rows(), d.to_rows());
+        assert!(DistanceMatrix::from_lower_triangle(vec![5.0, 3.0], names.clone()).is_err());
+        // A ragged matrix cannot be packed
+        let ragged = vec![vec![0.0, 1.0, 2.0], vec![1.0, 0.0], vec![2.0, 3.0, 0.0]];
+        assert!(matches!(
+            DistanceMatrix::build(ragged, names),
+            Err(Error::Validation(_))
+        ));
+    }
 }
@@ -0,0 +1,66 @@
+use std::{fmt, io};
+
+/// Errors returned by speedytree
+#[derive(Debug)]
+pub enum Error {
+    /// The input could not be read
+    Io(io::Error),
+    /// The input is not in the expected format
+    Parse {
+        /// Line (1-based) where the problem was found
+        line: usize,
+        /// Description of the problem
+        message: String,
+    },
+    /// A binary distance matrix is not in the expected format
+    Format(String),
+    /// The distance matrix is not a valid input for the solvers
+    Validation(String),
+    /// A NaN or infinite value was found
+    Numerical(String),
+    /// The tree does not have the expected shape
+    TreeShape(String),
+    /// A solver parameter is out of range
+    InvalidParameter(String),
+}
+
+/// Result type used across the crate
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub(crate) fn parse(line: usize, message: impl Into<String>) -> Self {
+        Error::Parse {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "{err}"),
+            Error::Parse { line, message } => write!(f, "line {line}: {message}"),
+            Error::Format(message) => write!(f, "invalid binary distance matrix: {message}"),
+            Error::Validation(message) => write!(f, "invalid distance matrix: {message}"),
+            Error::Numerical(message) => write!(f, "numerical error: {message}"),
+            Error::TreeShape(message) => write!(f, "unexpected tree shape: {message}"),
+            Error::InvalidParameter(message) => write!(f, "invalid parameter: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
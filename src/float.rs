@@ -0,0 +1,107 @@
+use std::cmp::Ordering;
+use std::fmt::Debug;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Div, Mul, Sub, SubAssign};
+
+/// Floating point precision used by the solvers to store the distances
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Precision {
+    /// 64-bit floats
+    #[default]
+    Double,
+    /// 32-bit floats, which halve the memory of the distances but keep only about 7 significant digits
+    Single,
+}
+
+/// Floating point type the solvers are generic over (f64 or f32)
+pub(crate) trait Float:
+    Copy
+    + Debug
+    + Default
+    + PartialOrd
+    + Send
+    + Sync
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + AddAssign
+    + SubAssign
+    + Sum
+    + 'static
+{
+    const ZERO: Self;
+    const INFINITY: Self;
+    fn from_f64(x: f64) -> Self;
+    fn from_usize(n: usize) -> Self;
+    fn to_f64(self) -> f64;
+    fn total_cmp(&self, other: &Self) -> Ordering;
+    fn is_nan(self) -> bool;
+    /// Branch length of the tree, with the shortest decimal representation of the value,
+    /// so f32 lengths are not written with spurious digits
+    fn to_branch_length(self) -> f64;
+}
+
+impl Float for f64 {
+    const ZERO: Self = 0.0;
+    const INFINITY: Self = f64::INFINITY;
+    fn from_f64(x: f64) -> Self {
+        x
+    }
+    fn from_usize(n: usize) -> Self {
+        n as f64
+    }
+    fn to_f64(self) -> f64 {
+        self
+    }
+    fn total_cmp(&self, other: &Self) -> Ordering {
+        f64::total_cmp(self, other)
+    }
+    fn is_nan(self) -> bool {
+        f64::is_nan(self)
+    }
+    fn to_branch_length(self) -> f64 {
+        self
+    }
+}
+
+impl Float for f32 {
+    const ZERO: Self = 0.0;
+    const INFINITY: Self = f32::INFINITY;
+    fn from_f64(x: f64) -> Self {
+        x as f32
+    }
+    fn from_usize(n: usize) -> Self {
+        n as f32
+    }
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+    fn total_cmp(&self, other: &Self) -> Ordering {
+        f32::total_cmp(self, other)
+    }
+    fn is_nan(self) -> bool {
+        f32::is_nan(self)
+    }
+    fn to_branch_length(self) -> f64 {
+        if !self.is_finite() {
+            return self as f64;
+        }
+        let mut buffer = dtoa::Buffer::new();
+        buffer.format_finite(self).parse().expect("Valid float")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_branch_lengths() {
+        assert_eq!(0.1f32.to_branch_length(), 0.1);
+        assert_ne!(0.1f32 as f64, 0.1);
+        assert_eq!(0.1f64.to_branch_length(), 0.1);
+        assert_eq!((-2.5f32).to_branch_length(), -2.5);
+        assert!(f32::NAN.to_branch_length().is_nan());
+    }
+}
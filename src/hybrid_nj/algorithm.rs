@@ -1,37 +1,136 @@
 use crate::{
-    distances::DistanceMatrix, naive_nj::DataNaiveNJ, rapid_nj::DataRapidNJ, ResultBox, Tree,
+    distances::DistanceMatrix,
+    float::Float,
+    naive_nj::DataNaiveNJ,
+    rapid_nj::{DataRapidNJ, PhyloTree, QMatrix, RowStorage},
+    Result, Tree,
 };
 
+/// Number of recent iterations the average cost of the rapid search roughly follows. The switch
+/// cannot be undone, so a single expensive search must not trigger it, while the cost grows over
+/// hundreds of iterations.
+const SMOOTHING: f64 = 8.0;
+
+/// Canonical q values a visited row entry of the rapid search costs, unless set otherwise
+pub(crate) const VISIT_COST: f64 = 1.0;
+
+/// When the hybrid solver switches from the rapid search to the canonical one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Switch {
+    /// For this number of the last iterations, when as many leaves as those plus 3 are left
+    CanonicalIters(usize),
+    /// When the rapid search costs as much as the canonical one, every visited row entry costing
+    /// this many canonical q values (see [`RapidCost`])
+    Adaptive(f64),
+}
+
+/// Average cost of the iterations of the rapid search, counted in q values of the canonical search.
+///
+/// A canonical iteration evaluates the q value of every pair of leaves left, while a rapid one
+/// evaluates those of the row entries it visits before the bounds prune the rest of the rows.
+/// Every visited entry costs `visit_cost` q values: 1 when both evaluations are as expensive, more
+/// when the rapid one is slower, which makes the switch happen earlier. The row updates are not
+/// counted. The entries are counted by [`QMatrix::find_neighbors_counting`] instead of timed, so
+/// the switch, and hence the tree, do not depend on the machine or the number of threads.
+#[derive(Debug, Clone)]
+pub(crate) struct RapidCost {
+    visit_cost: f64,
+    average: Option<f64>,
+}
+
+impl RapidCost {
+    pub fn new(visit_cost: f64) -> Self {
+        RapidCost {
+            visit_cost,
+            average: None,
+        }
+    }
+    /// Add an iteration that visited this number of entries
+    pub fn record(&mut self, visited: usize) {
+        let cost = self.visit_cost * visited as f64;
+        self.average = Some(match self.average {
+            None => cost,
+            Some(average) => average + (cost - average) / SMOOTHING,
+        });
+    }
+    /// Whether the canonical search of n_leaves is cheaper than the recent rapid iterations
+    pub fn canonical_is_cheaper(&self, n_leaves: usize) -> bool {
+        let canonical = (n_leaves * n_leaves.saturating_sub(1) / 2) as f64;
+        self.average.is_some_and(|average| average >= canonical)
+    }
+}
+
+/// Join neighbors with the rapid search until the switch, and return the number of iterations done
+pub(crate) fn rapid_iterations<T: Float>(
+    q: &mut QMatrix<T>,
+    t: &mut PhyloTree,
+    switch: Switch,
+) -> usize {
+    let mut cost = match switch {
+        Switch::CanonicalIters(_) => None,
+        Switch::Adaptive(visit_cost) => Some(RapidCost::new(visit_cost)),
+    };
+    let mut iterations = 0;
+    while q.n_leaves() > 3 {
+        let switched = match switch {
+            Switch::CanonicalIters(naive_iters) => q.n_leaves() <= naive_iters + 3,
+            Switch::Adaptive(_) => cost
+                .as_ref()
+                .is_some_and(|cost| cost.canonical_is_cheaper(q.n_leaves())),
+        };
+        if switched {
+            break;
+        }
+        let (i, j) = match cost.as_mut() {
+            None => q.find_neighbors(),
+            Some(cost) => {
+                let (pair, visited) = q.find_neighbors_counting();
+                cost.record(visited);
+                pair
+            }
+        };
+        let (dist_ui, dist_uj) = q.new_node_distances(i, j);
+        t.merge_neighbors(i, j, dist_ui, dist_uj);
+        q.update(i, j);
+        iterations += 1;
+    }
+    iterations
+}
+
 /// This approach is a hybrid between the naive neighbor joining and the rapid neighbor joining.
-/// If `naive_iters` is greater than n, then this function calls `naive_neighbor_joining` instead.
-/// If `naive_iters` is less than 4, then this function calls `rapid_nj` instead.
+/// Neighbor joining of n leaves takes n - 3 iterations, the last ones done by the naive algorithm.
+/// With `Switch::CanonicalIters(k)`, the naive algorithm is called alone when k >= n - 3, and
+/// `rapid_nj` when k = 0.
 /// Arguments:
 /// * `dist` - Distance matrix
-/// * `naive_iters` - Number of iterations to use the naive neighbor joining algorithm
+/// * `switch` - When to switch to the naive neighbor joining algorithm
+/// * `chunk_size` - Rows searched by every worker of the rapid phase
+/// * `row_storage` - How the sorted rows of the rapid phase are stored
 ///
 /// Returns:
 /// * `Ok(Tree)` - A phylogenetic tree
-/// * `Err(Box<dyn error::Error>)` - An error
-pub fn neighbor_joining(
+/// * `Err(Error)` - An error
+pub(crate) fn neighbor_joining<T: Float>(
     dist: DistanceMatrix,
-    naive_iters: usize,
+    switch: Switch,
     chunk_size: usize,
-) -> ResultBox<Tree> {
-    if dist.size() < 4 || naive_iters >= dist.size() {
-        return crate::naive_nj::canonical_neighbor_joining(dist);
+    row_storage: RowStorage,
+) -> Result<Tree> {
+    if dist.size() < 4 {
+        return crate::naive_nj::canonical_neighbor_joining::<T>(dist);
     }
-    if naive_iters < 4 {
-        return crate::rapid_nj::rapid_nj(dist, chunk_size);
+    if let Switch::CanonicalIters(naive_iters) = switch {
+        if naive_iters >= dist.size() - 3 {
+            return crate::naive_nj::canonical_neighbor_joining::<T>(dist);
+        }
+        if naive_iters == 0 {
+            return crate::rapid_nj::rapid_nj::<T>(dist, chunk_size, row_storage);
+        }
     }
-    let mut q = crate::rapid_nj::QMatrix::from(&dist);
-    let mut t = crate::rapid_nj::PhyloTree::build(&dist.names);
+    let mut t = PhyloTree::build(&dist.names);
+    let mut q = QMatrix::<T>::with_rows(dist, row_storage);
     q.set_chunk_size(chunk_size);
-    while q.n_leaves() > naive_iters {
-        let (i, j) = q.find_neighbors();
-        let (dist_ui, dist_uj) = q.new_node_distances(i, j);
-        t.merge_neighbors(i, j, dist_ui, dist_uj);
-        q.update(i, j);
-    }
+    rapid_iterations(&mut q, &mut t, switch);
     // Convert to the inner data structure of the naive neighbor joining
     let data = DataNaiveNJ::from(DataRapidNJ::new(q, t));
     let mut q = data.qmatrix;
@@ -1,14 +1,16 @@
 use std::collections::HashMap;
 
+use crate::float::Float;
 use crate::naive_nj::DataNaiveNJ;
+use crate::triangular::LowerTriangle;
 
-impl From<crate::rapid_nj::DataRapidNJ> for crate::naive_nj::DataNaiveNJ {
-    fn from(data: crate::rapid_nj::DataRapidNJ) -> Self {
+impl<T: Float> From<crate::rapid_nj::DataRapidNJ<T>> for crate::naive_nj::DataNaiveNJ<T> {
+    fn from(data: crate::rapid_nj::DataRapidNJ<T>) -> Self {
         let q = data.qmatrix;
         let tree = data.phylo_tree;
         let n = q.n_leaves();
         let mut nodes = HashMap::with_capacity(n);
-        let mut sum_cols: Vec<f64> = Vec::with_capacity(n);
+        let mut sum_cols: Vec<T> = Vec::with_capacity(n);
         let mut unmerged_index: Vec<usize> = Vec::with_capacity(n);
         for (index, elm) in q.sum_cols.iter().enumerate() {
             if let Some(elm) = elm {
@@ -20,14 +22,16 @@ impl From<crate::rapid_nj::DataRapidNJ> for crate::naive_nj::DataNaiveNJ {
         for (index, prev_index) in unmerged_index.iter().enumerate() {
             nodes.insert(index, tree.nodes[prev_index]);
         }
-        // Create a vector of n vectors of n f64 with zeros
-        let mut matrix: Vec<Vec<f64>> = vec![vec![0.0; n]; n];
+        // Pack the distances between the unmerged nodes into a lower triangle
+        let mut matrix = LowerTriangle::zeros(n);
         for (i, prev_i) in unmerged_index.iter().enumerate() {
-            for (j, prev_j) in unmerged_index.iter().enumerate() {
-                matrix[i][j] = q.distance(*prev_i, *prev_j);
+            let row = matrix.row_mut(i);
+            for (value, prev_j) in row.iter_mut().zip(&unmerged_index) {
+                *value = q.distance(*prev_i, *prev_j);
             }
         }
-        let qmatrix = crate::naive_nj::QMatrix::new(matrix, sum_cols);
+        drop(q);
+        let qmatrix = crate::naive_nj::QMatrix::new(matrix, sum_cols, unmerged_index);
         let phylo_tree = crate::naive_nj::PhyloTree::new(tree.tree, nodes);
         DataNaiveNJ {
             qmatrix,
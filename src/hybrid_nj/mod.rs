@@ -1,6 +1,6 @@
 mod algorithm;
 mod data;
-pub use algorithm::neighbor_joining;
+pub(crate) use algorithm::{neighbor_joining, Switch, VISIT_COST};
 
 #[cfg(test)]
 mod tests {
@@ -8,23 +8,24 @@ mod tests {
     use crate::distances::DistanceMatrix;
     #[test]
     fn test_example_wikipedia() {
-        let d = DistanceMatrix {
-            matrix: vec![
+        let d = DistanceMatrix::build(
+            vec![
                 vec![0.0, 5.0, 9.0, 9.0, 8.0],
                 vec![5.0, 0.0, 10.0, 10.0, 9.0],
                 vec![9.0, 10.0, 0.0, 8.0, 7.0],
                 vec![9.0, 10.0, 8.0, 0.0, 3.0],
                 vec![8.0, 9.0, 7.0, 3.0, 0.0],
             ],
-            names: vec![
+            vec![
                 "A".to_string(),
                 "B".to_string(),
                 "C".to_string(),
                 "D".to_string(),
                 "E".to_string(),
             ],
-        };
-        let phylo = neighbor_joining(d, 4, 1);
+        )
+        .unwrap();
+        let phylo = neighbor_joining::<f64>(d, Switch::CanonicalIters(1), 1, Default::default());
         assert!(phylo.is_ok());
         let tree = phylo.unwrap();
         let mut node_indices = tree.node_indices();
@@ -53,4 +54,76 @@ mod tests {
             assert_eq!(tree.edge_weight(tree.find_edge(a, b).unwrap()), Some(&dist));
         }
     }
+
+    #[test]
+    fn test_adaptive_switch_cost() {
+        let mut cost = algorithm::RapidCost::new(1.0);
+        // Nothing is known before the first iteration
+        assert!(!cost.canonical_is_cheaper(10));
+        // 1000 leaves, which cost 499500 canonical q values, and a few entries of every row
+        cost.record(5000);
+        assert!(!cost.canonical_is_cheaper(1000));
+        // A single iteration that visits many entries is not enough
+        cost.record(2_000_000);
+        assert!(!cost.canonical_is_cheaper(996));
+        // A few more raise the average past the canonical cost
+        for _ in 0..3 {
+            cost.record(2_000_000);
+        }
+        assert!(cost.canonical_is_cheaper(995));
+        // Costlier entries switch earlier
+        let mut cost = algorithm::RapidCost::new(200.0);
+        cost.record(5000);
+        assert!(cost.canonical_is_cheaper(1000));
+    }
+
+    #[test]
+    fn test_adaptive_switch_waits_on_tree_like_matrices() {
+        use crate::property_tests::random_additive_tree::{
+            distance_matrix_from_tree, random_unrooted_binary_tree,
+        };
+        use crate::rapid_nj::{PhyloTree, QMatrix, RowStorage};
+        let n = 200;
+        let d = distance_matrix_from_tree(random_unrooted_binary_tree(n));
+        for row_storage in [RowStorage::BTree, RowStorage::SortedArray] {
+            let mut t = PhyloTree::build(&d.names);
+            let mut q = QMatrix::<f64>::with_rows(d.clone(), row_storage);
+            let iterations =
+                algorithm::rapid_iterations(&mut q, &mut t, Switch::Adaptive(VISIT_COST));
+            // The rapid search prunes most of the rows until few leaves are left
+            assert!(iterations > n / 2, "{iterations}");
+        }
+    }
+
+    #[test]
+    fn test_adaptive_switch_is_independent_of_threads() {
+        use crate::rapid_nj::{PhyloTree, QMatrix, RowStorage};
+        use rand::{Rng, SeedableRng};
+        let mut rng = rand::rngs::StdRng::seed_from_u64(23);
+        let n = 150;
+        // Small integer distances, so the searches visit many entries
+        let d = crate::property_tests::symmetric_matrix(n, |_, _| rng.gen_range(1..20) as f64);
+        let rapid_iterations = |threads: usize, chunk_size: usize, row_storage: RowStorage| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            pool.install(|| {
+                let mut t = PhyloTree::build(&d.names);
+                let mut q = QMatrix::<f64>::with_rows(d.clone(), row_storage);
+                q.set_chunk_size(chunk_size);
+                algorithm::rapid_iterations(&mut q, &mut t, Switch::Adaptive(VISIT_COST))
+            })
+        };
+        for row_storage in [RowStorage::BTree, RowStorage::SortedArray] {
+            let iterations = rapid_iterations(1, n, row_storage);
+            assert!(0 < iterations && iterations < n - 3, "{iterations}");
+            for (threads, chunk_size) in [(1, 1), (4, 1), (4, 7), (8, n / 8)] {
+                assert_eq!(
+                    rapid_iterations(threads, chunk_size, row_storage),
+                    iterations
+                );
+            }
+        }
+    }
 }
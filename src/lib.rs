@@ -1,18 +1,18 @@
 //! Canonical and RapidNJ implementations of Neighbor-joining in Rust
 //!
-//! Speedytree is a Rust implementation of Neighbor-Joining for building phylogenetic trees from large Phylip distance matrices. 
-//! 
+//! Speedytree is a Rust implementation of Neighbor-Joining for building phylogenetic trees from large Phylip distance matrices.
+//!
 //! There are two strategies: the Canonical algorithm (as [QuickTree](https://github.com/khowe/quicktree)) and something in the spirit of [RapidNJ](https://birc.au.dk/software/rapidnj/) but with B-trees.
-//! You can read more about Neighbor-Joining [here](https://en.wikipedia.org/wiki/Neighbor_joining). The RapidNJ algorithm should be faster for very big problems at the cost of a larger memory overhead. 
-//! 
+//! You can read more about Neighbor-Joining [here](https://en.wikipedia.org/wiki/Neighbor_joining). The RapidNJ algorithm should be faster for very big problems at the cost of a larger memory overhead.
+//!
 //! A command line application (that reads PHYLIP distance matrix) is also provided. Please, read more in the [GitHub repository](https://github.com/currocam/speedytree). You will also find a few slides I made there.
-//! 
+//!
 //! ## Example
 //! A minimal example of the library is provided here. You can read more about the command line app by running speedytree -h
 //! ```
 //! use speedytree::DistanceMatrix;
 //! use speedytree::robinson_foulds;
-//! use speedytree::{NeighborJoiningSolver, Canonical, RapidBtrees, Hybrid};
+//! use speedytree::{NeighborJoiningSolver, Auto, Canonical, RapidBtrees, Hybrid};
 //! use rayon;
 //!// Raw Phylip format
 //!let input = "5
@@ -31,12 +31,13 @@
 //! let tree2 = NeighborJoiningSolver::<RapidBtrees>::default(d.clone())
 //!   .solve()
 //!   .unwrap();
-//! assert_eq!(robinson_foulds(&tree1, &tree2), 0);
+//! assert_eq!(robinson_foulds(&tree1, &tree2).unwrap(), 0);
 //!
 //! // You can improve the speed a lot by using multiple threads (see rayon::ThreadPoolBuilder::new())
 //! // If so, you may want to tune the chunk size every worker uses
 //!   let tree3 = NeighborJoiningSolver::<RapidBtrees>::default(d.clone())
 //!   .set_chunk_size(4)
+//!   .unwrap()
 //!   .solve()
 //!   .unwrap();
 //! // An algorithm that starts using RapidBtrees, later uses Canonical
@@ -45,22 +46,48 @@
 //!   .set_canonical_steps(2)
 //!   .solve()
 //!   .unwrap();
-//! assert_eq!(robinson_foulds(&tree3, &tree4), 0);
+//! assert_eq!(robinson_foulds(&tree3, &tree4).unwrap(), 0);
+//!
+//! // Or let the solver choose the strategy from the size of the problem
+//! let solver = NeighborJoiningSolver::<Auto>::default(d.clone());
+//! println!("{}", solver.plan().unwrap());
+//! let tree5 = solver.solve().unwrap();
+//! assert_eq!(robinson_foulds(&tree1, &tree5).unwrap(), 0);
 //! ```
 
+mod alignment;
+mod auto;
+mod binary;
+mod bionj;
+mod branches;
+mod candidate;
+mod clustering;
 mod distances;
+mod error;
+mod float;
 mod hybrid_nj;
 mod naive_nj;
 mod newick;
 /// Property tests for neighbor joining algorithm
 mod property_tests;
 mod rapid_nj;
-pub use distances::DistanceMatrix;
+mod sketch;
+mod triangular;
+mod unj;
+mod validation;
+pub use alignment::{Alignment, DistanceCalculator, DistanceModel};
+pub use auto::{Plan, Strategy};
+pub use branches::NegativeBranches;
+pub use clustering::Linkage;
+pub use distances::{DistanceMatrix, PhylipNames};
+pub use error::{Error, Result};
+pub use float::Precision;
 pub use newick::to_newick;
 pub use property_tests::tree_distances::{branch_score, robinson_foulds};
+pub use rapid_nj::RowStorage;
+pub use sketch::{Sketch, SketchMethod, Sketcher};
+pub use validation::{ValidationAction, ValidationPolicy, ValidationReport};
 
-use std::error;
-type ResultBox<T> = std::result::Result<T, Box<dyn error::Error>>;
 /// An undirected network built in top of [Petgraph](https://github.com/petgraph/petgraph). Internal nodes have empty names.
 pub type Tree = petgraph::graph::UnGraph<String, f64>;
 
@@ -68,36 +95,92 @@ pub type Tree = petgraph::graph::UnGraph<String, f64>;
 pub struct NeighborJoiningSolver<U> {
     algo: U,
     dist: DistanceMatrix,
+    negative_branches: NegativeBranches,
+}
+impl<U> NeighborJoiningSolver<U> {
+    /// Set what to do with negative branch lengths (by default, they are kept)
+    pub fn set_negative_branches(self, negative_branches: NegativeBranches) -> Self {
+        NeighborJoiningSolver {
+            negative_branches,
+            ..self
+        }
+    }
+}
+/// Tree built by a solver, and how many of its branch lengths were adjusted
+#[derive(Debug, Clone)]
+pub struct Solution {
+    /// Phylogenetic tree
+    pub tree: Tree,
+    /// Negative branch lengths changed by the policy of
+    /// [`set_negative_branches`](NeighborJoiningSolver::set_negative_branches), none when they are kept
+    pub adjusted_branches: usize,
+}
+fn adjust_branches(tree: Result<Tree>, negative_branches: NegativeBranches) -> Result<Solution> {
+    let mut tree = tree?;
+    let adjusted_branches = negative_branches.apply(&mut tree);
+    Ok(Solution {
+        tree,
+        adjusted_branches,
+    })
 }
 /// Canonical Neighbor-Joining, similar to [QuickTree](https://github.com/khowe/quicktree). It runs on cubic time (worst and best case). It uses quadratic memory.  
-pub struct Canonical {}
+pub struct Canonical {
+    precision: Precision,
+}
 impl NeighborJoiningSolver<Canonical> {
     /// Construct solver from parameters
     pub fn build(dist: DistanceMatrix) -> Self {
         NeighborJoiningSolver {
-            algo: Canonical {},
+            algo: Canonical {
+                precision: Precision::default(),
+            },
             dist,
+            negative_branches: NegativeBranches::default(),
         }
     }
     /// Default solver
     pub fn default(dist: DistanceMatrix) -> Self {
         Self::build(dist)
     }
+    /// Set the precision of the distances (double by default, single halves the memory)
+    pub fn set_precision(self, precision: Precision) -> Self {
+        NeighborJoiningSolver {
+            algo: Canonical { precision },
+            ..self
+        }
+    }
     /// Solve the Neighbor-Joining problem
-    pub fn solve(self) -> ResultBox<Tree> {
-        naive_nj::canonical_neighbor_joining(self.dist)
+    pub fn solve(self) -> Result<Tree> {
+        Ok(self.solve_with_report()?.tree)
+    }
+    /// Solve the Neighbor-Joining problem, and report how many negative branch lengths were adjusted
+    pub fn solve_with_report(self) -> Result<Solution> {
+        self.dist.check_solvable()?;
+        let negative_branches = self.negative_branches;
+        let tree = match self.algo.precision {
+            Precision::Double => naive_nj::canonical_neighbor_joining::<f64>(self.dist),
+            Precision::Single => naive_nj::canonical_neighbor_joining::<f32>(self.dist),
+        };
+        adjust_branches(tree, negative_branches)
     }
 }
 /// In the spirit of [RapidNJ](https://birc.au.dk/software/rapidnj/), but with B-trees. It runs on n^2 log(n) time best case and cubic time worst case.  It uses quadratic memory (with a higher constant).
 pub struct RapidBtrees {
     chunk_size: usize,
+    row_storage: RowStorage,
+    precision: Precision,
 }
 impl NeighborJoiningSolver<RapidBtrees> {
     /// Construct solver from parameters
     pub fn build(dist: DistanceMatrix, chunk_size: usize) -> Self {
         NeighborJoiningSolver {
-            algo: RapidBtrees { chunk_size },
+            algo: RapidBtrees {
+                chunk_size,
+                row_storage: RowStorage::default(),
+                precision: Precision::default(),
+            },
             dist,
+            negative_branches: NegativeBranches::default(),
         }
     }
     /// Default solver (based on available rayon threads)
@@ -105,81 +188,403 @@ impl NeighborJoiningSolver<RapidBtrees> {
         let n = dist.size();
         let threads = rayon::current_num_threads();
         let chunk_size = std::cmp::max(n / threads, 1);
-        if chunk_size == 0 {
-            panic!("Chunk size cannot be zero.");
-        }
         NeighborJoiningSolver {
-            algo: RapidBtrees { chunk_size },
+            algo: RapidBtrees {
+                chunk_size,
+                row_storage: RowStorage::default(),
+                precision: Precision::default(),
+            },
             dist,
+            negative_branches: NegativeBranches::default(),
         }
     }
     /// Set chunk size (for every worker)
-    pub fn set_chunk_size(self, chunk_size: usize) -> Self {
+    pub fn set_chunk_size(self, chunk_size: usize) -> Result<Self> {
         if chunk_size < 1 {
-            panic!("Chunk size  must be > 0.");
+            return Err(Error::InvalidParameter(
+                "chunk size must be > 0".to_string(),
+            ));
+        }
+        Ok(NeighborJoiningSolver {
+            algo: RapidBtrees {
+                chunk_size,
+                ..self.algo
+            },
+            ..self
+        })
+    }
+    /// Set how the sorted rows are stored (B-trees by default, sorted arrays use less memory)
+    pub fn set_row_storage(self, row_storage: RowStorage) -> Self {
+        NeighborJoiningSolver {
+            algo: RapidBtrees {
+                row_storage,
+                ..self.algo
+            },
+            ..self
+        }
+    }
+    /// Set the precision of the distances (double by default, single halves the memory)
+    pub fn set_precision(self, precision: Precision) -> Self {
+        NeighborJoiningSolver {
+            algo: RapidBtrees {
+                precision,
+                ..self.algo
+            },
+            ..self
         }
-        Self::build(self.dist, chunk_size)
     }
     /// Solve the Neighbor-Joining problem
-    pub fn solve(self) -> ResultBox<Tree> {
-        rapid_nj::rapid_nj(self.dist, self.algo.chunk_size)
+    pub fn solve(self) -> Result<Tree> {
+        Ok(self.solve_with_report()?.tree)
+    }
+    /// Solve the Neighbor-Joining problem, and report how many negative branch lengths were adjusted
+    pub fn solve_with_report(self) -> Result<Solution> {
+        self.dist.check_solvable()?;
+        let negative_branches = self.negative_branches;
+        let RapidBtrees {
+            chunk_size,
+            row_storage,
+            precision,
+        } = self.algo;
+        let tree = match precision {
+            Precision::Double => rapid_nj::rapid_nj::<f64>(self.dist, chunk_size, row_storage),
+            Precision::Single => rapid_nj::rapid_nj::<f32>(self.dist, chunk_size, row_storage),
+        };
+        adjust_branches(tree, negative_branches)
+    }
+}
+
+/// [BIONJ](https://doi.org/10.1093/oxfordjournals.molbev.a025808) (Gascuel 1997). It chooses neighbors as the Canonical algorithm, but the distances to every new node are weighted to minimize their variance. It runs on cubic time and uses twice the memory of Canonical.
+pub struct BioNJ {}
+impl NeighborJoiningSolver<BioNJ> {
+    /// Construct solver from parameters
+    pub fn build(dist: DistanceMatrix) -> Self {
+        NeighborJoiningSolver {
+            algo: BioNJ {},
+            dist,
+            negative_branches: NegativeBranches::default(),
+        }
+    }
+    /// Default solver
+    pub fn default(dist: DistanceMatrix) -> Self {
+        Self::build(dist)
+    }
+    /// Solve the BIONJ problem
+    pub fn solve(self) -> Result<Tree> {
+        Ok(self.solve_with_report()?.tree)
+    }
+    /// Solve the BIONJ problem, and report how many negative branch lengths were adjusted
+    pub fn solve_with_report(self) -> Result<Solution> {
+        self.dist.check_solvable()?;
+        let negative_branches = self.negative_branches;
+        adjust_branches(bionj::bionj(self.dist), negative_branches)
+    }
+}
+
+/// Unweighted neighbor joining, UNJ (Gascuel 1997). It chooses neighbors as the Canonical algorithm, but the distances to every new node are weighted by the number of taxa in each subtree. Useful when the sampling is very uneven. It runs on cubic time.
+pub struct Unweighted {}
+impl NeighborJoiningSolver<Unweighted> {
+    /// Construct solver from parameters
+    pub fn build(dist: DistanceMatrix) -> Self {
+        NeighborJoiningSolver {
+            algo: Unweighted {},
+            dist,
+            negative_branches: NegativeBranches::default(),
+        }
+    }
+    /// Default solver
+    pub fn default(dist: DistanceMatrix) -> Self {
+        Self::build(dist)
+    }
+    /// Solve the UNJ problem
+    pub fn solve(self) -> Result<Tree> {
+        Ok(self.solve_with_report()?.tree)
+    }
+    /// Solve the UNJ problem, and report how many negative branch lengths were adjusted
+    pub fn solve_with_report(self) -> Result<Solution> {
+        self.dist.check_solvable()?;
+        let negative_branches = self.negative_branches;
+        adjust_branches(
+            unj::unweighted_neighbor_joining(self.dist),
+            negative_branches,
+        )
+    }
+}
+
+/// Agglomerative clustering (UPGMA, WPGMA, single or complete linkage). Unlike neighbor joining, it returns a rooted ultrametric tree, where the root is the only internal node with two edges. It runs on cubic time.
+pub struct Clustering {
+    linkage: Linkage,
+}
+impl NeighborJoiningSolver<Clustering> {
+    /// Construct solver from parameters
+    pub fn build(dist: DistanceMatrix, linkage: Linkage) -> Self {
+        NeighborJoiningSolver {
+            algo: Clustering { linkage },
+            dist,
+            negative_branches: NegativeBranches::default(),
+        }
+    }
+    /// Default solver (UPGMA)
+    pub fn default(dist: DistanceMatrix) -> Self {
+        Self::build(dist, Linkage::default())
+    }
+    /// Set the linkage between clusters
+    pub fn set_linkage(self, linkage: Linkage) -> Self {
+        NeighborJoiningSolver {
+            algo: Clustering { linkage },
+            ..self
+        }
+    }
+    /// Solve the clustering problem
+    pub fn solve(self) -> Result<Tree> {
+        Ok(self.solve_with_report()?.tree)
+    }
+    /// Solve the clustering problem, and report how many negative branch lengths were adjusted
+    pub fn solve_with_report(self) -> Result<Solution> {
+        self.dist.check_solvable()?;
+        let negative_branches = self.negative_branches;
+        adjust_branches(
+            clustering::agglomerative_clustering(self.dist, self.algo.linkage),
+            negative_branches,
+        )
     }
 }
 
-/// A mix of the Canonical and RapidBtrees. First, it starts with RapidBtrees (less lookups, but with an overhead), and then it changes the strategy. 
+/// A mix of the Canonical and RapidBtrees. First, it starts with RapidBtrees (less lookups, but with an overhead), and then it changes the strategy.
+/// Neighbor joining of n taxa takes n - 3 iterations, and the last `canonical_iters` of them are done by Canonical.
 pub struct Hybrid {
     chunk_size: usize,
-    canonical_iters: usize,
+    switch: hybrid_nj::Switch,
+    row_storage: RowStorage,
+    precision: Precision,
 }
 impl NeighborJoiningSolver<Hybrid> {
-    /// Construct solver from parameters
+    /// Construct solver from parameters, with the number of the last iterations done by Canonical
     pub fn build(dist: DistanceMatrix, chunk_size: usize, canonical_iters: usize) -> Self {
         NeighborJoiningSolver {
             algo: Hybrid {
                 chunk_size,
-                canonical_iters,
+                switch: hybrid_nj::Switch::CanonicalIters(canonical_iters),
+                row_storage: RowStorage::default(),
+                precision: Precision::default(),
             },
             dist,
+            negative_branches: NegativeBranches::default(),
         }
     }
-    /// Default solver (based on available rayon threads and problem size)
+    /// Default solver (based on available rayon threads and problem size), with half of the
+    /// iterations done by Canonical
     pub fn default(dist: DistanceMatrix) -> Self {
         let n = dist.size();
         let threads = rayon::current_num_threads();
         let chunk_size = std::cmp::max(n / threads, 1);
-        let canonical_iters = std::cmp::max(n / 2, 1);
+        let canonical_iters = canonical_iters(n, 0.5);
         NeighborJoiningSolver {
             algo: Hybrid {
                 chunk_size,
-                canonical_iters,
+                switch: hybrid_nj::Switch::CanonicalIters(canonical_iters),
+                row_storage: RowStorage::default(),
+                precision: Precision::default(),
             },
             dist,
+            negative_branches: NegativeBranches::default(),
         }
     }
     /// Solve the Neighbor-Joining problem
-    pub fn solve(self) -> ResultBox<Tree> {
-        hybrid_nj::neighbor_joining(self.dist, self.algo.canonical_iters, self.algo.chunk_size)
+    pub fn solve(self) -> Result<Tree> {
+        Ok(self.solve_with_report()?.tree)
+    }
+    /// Solve the Neighbor-Joining problem, and report how many negative branch lengths were adjusted
+    pub fn solve_with_report(self) -> Result<Solution> {
+        self.dist.check_solvable()?;
+        let negative_branches = self.negative_branches;
+        let Hybrid {
+            chunk_size,
+            switch,
+            row_storage,
+            precision,
+        } = self.algo;
+        let tree = match precision {
+            Precision::Double => {
+                hybrid_nj::neighbor_joining::<f64>(self.dist, switch, chunk_size, row_storage)
+            }
+            Precision::Single => {
+                hybrid_nj::neighbor_joining::<f32>(self.dist, switch, chunk_size, row_storage)
+            }
+        };
+        adjust_branches(tree, negative_branches)
+    }
+    /// Set how the sorted rows of the RapidBtrees phase are stored (B-trees by default)
+    pub fn set_row_storage(self, row_storage: RowStorage) -> Self {
+        NeighborJoiningSolver {
+            algo: Hybrid {
+                row_storage,
+                ..self.algo
+            },
+            ..self
+        }
+    }
+    /// Set the precision of the distances (double by default, single halves the memory)
+    pub fn set_precision(self, precision: Precision) -> Self {
+        NeighborJoiningSolver {
+            algo: Hybrid {
+                precision,
+                ..self.algo
+            },
+            ..self
+        }
     }
     /// Set chunk size (for every worker)
-    pub fn set_chunk_size(self, chunk_size: usize) -> Self {
+    pub fn set_chunk_size(self, chunk_size: usize) -> Result<Self> {
         if chunk_size < 1 {
-            panic!("Chunk size  must be > 0.");
+            return Err(Error::InvalidParameter(
+                "chunk size must be > 0".to_string(),
+            ));
         }
-        Self::build(self.dist, chunk_size, self.algo.canonical_iters)
+        Ok(NeighborJoiningSolver {
+            algo: Hybrid {
+                chunk_size,
+                ..self.algo
+            },
+            ..self
+        })
     }
-    /// Set number of canonical iterations will be done
+    /// Set the number of the last iterations done by Canonical: 0 for RapidBtrees only, and n - 3
+    /// or more for Canonical only
     pub fn set_canonical_steps(self, n: usize) -> Self {
-        if n < 1 {
-            panic!("n must be > 0.");
+        NeighborJoiningSolver {
+            algo: Hybrid {
+                switch: hybrid_nj::Switch::CanonicalIters(n),
+                ..self.algo
+            },
+            ..self
+        }
+    }
+    /// Switch to Canonical when it becomes cheaper than the RapidBtrees search, judging from the
+    /// row entries visited by the last iterations, instead of after a fixed number of iterations.
+    /// Every visited entry costs as much as a q value of Canonical.
+    pub fn set_adaptive_switch(self) -> Self {
+        NeighborJoiningSolver {
+            algo: Hybrid {
+                switch: hybrid_nj::Switch::Adaptive(hybrid_nj::VISIT_COST),
+                ..self.algo
+            },
+            ..self
+        }
+    }
+    /// Switch adaptively (see `set_adaptive_switch`), with every visited entry costing this many q
+    /// values of Canonical. Larger costs switch earlier, for row storages slower to search.
+    pub fn set_adaptive_switch_cost(self, visit_cost: f64) -> Result<Self> {
+        if !(visit_cost.is_finite() && visit_cost > 0.0) {
+            return Err(Error::InvalidParameter(
+                "visit cost must be positive and finite".to_string(),
+            ));
         }
-        Self::build(self.dist, self.algo.chunk_size, n)
+        Ok(NeighborJoiningSolver {
+            algo: Hybrid {
+                switch: hybrid_nj::Switch::Adaptive(visit_cost),
+                ..self.algo
+            },
+            ..self
+        })
     }
-    /// Set fraction of canonical iterations will be done
-    pub fn set_canonical_percentage(self, prop: f64) -> Self {
-        if prop <= 0.0 || prop >= 1.0 {
-            panic!("Proportion must be between 0 and 1.");
+    /// Set the fraction of the iterations done by Canonical, from 0 (RapidBtrees only) to 1
+    /// (Canonical only). The number of iterations is rounded to the nearest integer.
+    pub fn set_canonical_percentage(self, prop: f64) -> Result<Self> {
+        if !(0.0..=1.0).contains(&prop) {
+            return Err(Error::InvalidParameter(
+                "proportion must be between 0 and 1".to_string(),
+            ));
+        }
+        let canonical_iters = canonical_iters(self.dist.size(), prop);
+        Ok(self.set_canonical_steps(canonical_iters))
+    }
+}
+
+/// Number of iterations of neighbor joining of n taxa that make up the given fraction
+fn canonical_iters(n: usize, fraction: f64) -> usize {
+    (n.saturating_sub(3) as f64 * fraction).round() as usize
+}
+
+/// Chooses the strategy, the chunk size and the switch to Canonical from the number of taxa, the
+/// available rayon threads and an optional memory budget (see [`Plan`]). Canonical is used up to
+/// a few thousand taxa, and Hybrid with sorted arrays beyond.
+pub struct Auto {
+    memory_budget: Option<usize>,
+    precision: Option<Precision>,
+}
+impl NeighborJoiningSolver<Auto> {
+    /// Construct solver from parameters
+    pub fn build(dist: DistanceMatrix) -> Self {
+        NeighborJoiningSolver {
+            algo: Auto {
+                memory_budget: None,
+                precision: None,
+            },
+            dist,
+            negative_branches: NegativeBranches::default(),
+        }
+    }
+    /// Default solver (without memory budget)
+    pub fn default(dist: DistanceMatrix) -> Self {
+        Self::build(dist)
+    }
+    /// Set the memory in bytes the solver may use, besides the distance matrix
+    pub fn set_memory_budget(self, bytes: usize) -> Result<Self> {
+        if bytes < 1 {
+            return Err(Error::InvalidParameter(
+                "memory budget must be > 0".to_string(),
+            ));
+        }
+        Ok(NeighborJoiningSolver {
+            algo: Auto {
+                memory_budget: Some(bytes),
+                ..self.algo
+            },
+            ..self
+        })
+    }
+    /// Set the precision of the distances. By default, it is double unless only single precision
+    /// fits in the memory budget.
+    pub fn set_precision(self, precision: Precision) -> Self {
+        NeighborJoiningSolver {
+            algo: Auto {
+                precision: Some(precision),
+                ..self.algo
+            },
+            ..self
+        }
+    }
+    /// Strategy that will be used to solve the problem
+    pub fn plan(&self) -> Result<Plan> {
+        Plan::choose(
+            self.dist.size(),
+            rayon::current_num_threads(),
+            self.algo.memory_budget,
+            self.algo.precision,
+        )
+    }
+    /// Solve the Neighbor-Joining problem with the chosen strategy
+    pub fn solve(self) -> Result<Tree> {
+        Ok(self.solve_with_report()?.tree)
+    }
+    /// Solve the Neighbor-Joining problem with the chosen strategy, and report how many negative branch lengths were adjusted
+    pub fn solve_with_report(self) -> Result<Solution> {
+        let plan = self.plan()?;
+        match plan.strategy {
+            Strategy::Canonical => NeighborJoiningSolver::<Canonical>::build(self.dist)
+                .set_precision(plan.precision)
+                .set_negative_branches(self.negative_branches)
+                .solve_with_report(),
+            Strategy::Hybrid {
+                chunk_size,
+                canonical_iters,
+                row_storage,
+            } => NeighborJoiningSolver::<Hybrid>::build(self.dist, chunk_size, canonical_iters)
+                .set_row_storage(row_storage)
+                .set_precision(plan.precision)
+                .set_negative_branches(self.negative_branches)
+                .solve_with_report(),
         }
-        let n = self.dist.size() as f64 * prop / 100.0;
-        Self::build(self.dist, self.algo.chunk_size, n as usize)
     }
 }
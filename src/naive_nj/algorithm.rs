@@ -1,10 +1,10 @@
-use crate::{distances::DistanceMatrix, ResultBox, Tree};
+use crate::{distances::DistanceMatrix, float::Float, Result, Tree};
 
 use super::{phylo_tree::PhyloTree, qmatrix::QMatrix};
 
-pub fn canonical_neighbor_joining(dist: DistanceMatrix) -> ResultBox<Tree> {
+pub fn canonical_neighbor_joining<T: Float>(dist: DistanceMatrix) -> Result<Tree> {
     let mut t = PhyloTree::build(&dist.names);
-    let mut q = QMatrix::build(dist);
+    let mut q = QMatrix::<T>::build(dist);
     while q.n_leaves() > 3 {
         // Find the minimum element in the distance matrix
         let (i, j) = q.find_neighbors();
@@ -16,18 +16,19 @@ pub fn canonical_neighbor_joining(dist: DistanceMatrix) -> ResultBox<Tree> {
     Ok(terminate_nj(t, q))
 }
 
-pub(crate) fn terminate_nj(tree: PhyloTree, q: QMatrix) -> Tree {
+pub(crate) fn terminate_nj<T: Float>(tree: PhyloTree, q: QMatrix<T>) -> Tree {
     let (i, j, m) = (tree.nodes[&0], tree.nodes[&1], tree.nodes[&2]);
     let mut tree = tree.tree;
 
-    let dvi = (q.distance(0, 1) + q.distance(0, 2) - q.distance(1, 2)) / 2.0;
-    let dvj = (q.distance(0, 1) + q.distance(1, 2) - q.distance(0, 2)) / 2.0;
-    let dvm = (q.distance(0, 2) + q.distance(1, 2) - q.distance(0, 1)) / 2.0;
+    let two = T::from_f64(2.0);
+    let dvi = (q.distance(0, 1) + q.distance(0, 2) - q.distance(1, 2)) / two;
+    let dvj = (q.distance(0, 1) + q.distance(1, 2) - q.distance(0, 2)) / two;
+    let dvm = (q.distance(0, 2) + q.distance(1, 2) - q.distance(0, 1)) / two;
 
     let v = tree.add_node("".to_owned());
-    tree.add_edge(v, i, dvi);
-    tree.add_edge(v, j, dvj);
-    tree.add_edge(v, m, dvm);
+    tree.add_edge(v, i, dvi.to_branch_length());
+    tree.add_edge(v, j, dvj.to_branch_length());
+    tree.add_edge(v, m, dvm.to_branch_length());
 
     tree
 }
@@ -37,24 +38,25 @@ mod tests {
     use super::*;
     #[test]
     fn test_example_wikipedia() {
-        let d = DistanceMatrix {
-            matrix: vec![
+        let d = DistanceMatrix::build(
+            vec![
                 vec![0.0, 5.0, 9.0, 9.0, 8.0],
                 vec![5.0, 0.0, 10.0, 10.0, 9.0],
                 vec![9.0, 10.0, 0.0, 8.0, 7.0],
                 vec![9.0, 10.0, 8.0, 0.0, 3.0],
                 vec![8.0, 9.0, 7.0, 3.0, 0.0],
             ],
-            names: vec![
+            vec![
                 "A".to_string(),
                 "B".to_string(),
                 "C".to_string(),
                 "D".to_string(),
                 "E".to_string(),
             ],
-        };
+        )
+        .unwrap();
 
-        let phylo = canonical_neighbor_joining(d);
+        let phylo = canonical_neighbor_joining::<f64>(d);
         assert!(phylo.is_ok());
 
         let tree = phylo.unwrap();
@@ -8,8 +8,8 @@ mod phylo_tree;
 pub use algorithm::canonical_neighbor_joining;
 pub(crate) use algorithm::terminate_nj;
 pub(crate) use phylo_tree::PhyloTree;
-pub(crate) use qmatrix::QMatrix;
-pub(crate) struct DataNaiveNJ {
-    pub qmatrix: qmatrix::QMatrix,
+pub(crate) use qmatrix::{merge_layout, QMatrix};
+pub(crate) struct DataNaiveNJ<T> {
+    pub qmatrix: qmatrix::QMatrix<T>,
     pub phylo_tree: phylo_tree::PhyloTree,
 }
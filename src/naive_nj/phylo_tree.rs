@@ -4,6 +4,8 @@ use std::collections::HashMap;
 // graph from petagraph
 use petgraph::{graph::UnGraph, stable_graph::NodeIndex};
 
+use crate::float::Float;
+
 #[derive(Debug, Clone)]
 pub struct PhyloTree {
     pub tree: crate::Tree,
@@ -38,7 +40,7 @@ impl PhyloTree {
         }
     }
 
-    pub fn merge_neighbors(&mut self, a: usize, b: usize, dau: f64, dbu: f64) -> NodeIndex {
+    pub fn merge_neighbors<T: Float>(&mut self, a: usize, b: usize, dau: T, dbu: T) -> NodeIndex {
         // Get nodes to merge
         let n: &usize = &self.n_unmerged_leaves;
         let a_node = self.nodes.remove(&a).expect("Valid node");
@@ -62,8 +64,8 @@ impl PhyloTree {
         self.n_unmerged_leaves -= 1;
 
         // Add new edges
-        self.tree.add_edge(u, a_node, dau);
-        self.tree.add_edge(u, b_node, dbu);
+        self.tree.add_edge(u, a_node, dau.to_branch_length());
+        self.tree.add_edge(u, b_node, dbu.to_branch_length());
         u
     }
 }
@@ -74,7 +76,7 @@ mod tests {
     use super::*;
     #[test]
     fn test_new() {
-        let tree = PhyloTree::build(&vec![
+        let tree = PhyloTree::build(&[
             "A".to_string(),
             "B".to_string(),
             "C".to_string(),
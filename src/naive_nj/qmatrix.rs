@@ -1,101 +1,259 @@
-use std::cmp;
+use rayon::prelude::*;
 
-use crate::distances::DistanceMatrix;
+use crate::{
+    candidate::Candidate, distances::DistanceMatrix, float::Float, triangular::LowerTriangle,
+};
+
+/// Number of values of the row search kept side by side, so the minimum is vectorised
+const LANES: usize = 8;
+/// Smallest share of the O(n) loops of an update given to a thread
+const MIN_UPDATE_LEN: usize = 4096;
 
 #[derive(Debug)]
-pub struct QMatrix {
-    matrix: Vec<Vec<f64>>,
-    sum_cols: Vec<f64>,
+pub struct QMatrix<T = f64> {
+    matrix: LowerTriangle<T>,
+    sum_cols: Vec<T>,
+    // Creation index of every row, to break ties (see Candidate)
+    ids: Vec<usize>,
+    next_id: usize,
 }
 
-impl QMatrix {
-    pub fn new(matrix: Vec<Vec<f64>>, sum_cols: Vec<f64>) -> Self {
-        Self { matrix, sum_cols }
+impl<T: Float> QMatrix<T> {
+    pub fn new(matrix: LowerTriangle<T>, sum_cols: Vec<T>, ids: Vec<usize>) -> Self {
+        let next_id = ids.iter().max().map_or(0, |id| id + 1);
+        Self {
+            matrix,
+            sum_cols,
+            ids,
+            next_id,
+        }
     }
     pub fn n_leaves(&self) -> usize {
-        self.matrix.len()
+        self.matrix.size()
+    }
+    pub fn distance(&self, i: usize, j: usize) -> T {
+        self.matrix.get(i, j)
     }
-    pub fn distance(&self, i: usize, j: usize) -> f64 {
-        self.matrix[i][j]
+    pub fn sum_col(&self, i: usize) -> T {
+        self.sum_cols[i]
     }
-    pub fn new_node_distances(&self, i: usize, j: usize) -> (f64, f64) {
-        let s = (self.n_leaves() - 2) as f64;
+    pub fn new_node_distances(&self, i: usize, j: usize) -> (T, T) {
+        let s = T::from_usize(self.n_leaves() - 2);
+        let two = T::from_f64(2.0);
         let dist_ui = self.distance(i, j) + self.sum_cols[i] / s - self.sum_cols[j] / s;
-        (dist_ui / 2.0, self.distance(i, j) - dist_ui / 2.0)
+        (dist_ui / two, self.distance(i, j) - dist_ui / two)
     }
 
     pub fn build(d: DistanceMatrix) -> Self {
-        let matrix = d.matrix;
-        let sum_cols = matrix
-            .iter()
-            .map(|row| row.iter().sum::<f64>())
-            .collect::<Vec<f64>>();
-        Self { matrix, sum_cols }
-    }
-    pub fn find_neighbors(&self) -> (usize, usize) {
-        let matrix = &self.matrix;
-        let sums = &self.sum_cols;
-        let n = matrix.len();
-        let mut neighbors = (0, 0);
-        let mut best_q = f64::INFINITY;
-
+        let matrix = d.into_triangle::<T>();
+        let n = matrix.size();
+        // Every row is summed in the order of the columns
+        let mut sum_cols = vec![T::ZERO; n];
         for i in 0..n {
-            for j in i + 1..n {
-                let q = (matrix[i][j] * (n - 2) as f64) - sums[i] - sums[j];
-                if q < best_q {
-                    best_q = q;
-                    neighbors = (i, j);
-                }
+            for (j, value) in matrix.row(i).iter().enumerate() {
+                sum_cols[i] += *value;
+                sum_cols[j] += *value;
             }
         }
-        (
-            cmp::min(neighbors.0, neighbors.1),
-            cmp::max(neighbors.0, neighbors.1),
-        )
+        let ids = (0..n).collect();
+        Self::new(matrix, sum_cols, ids)
+    }
+    pub fn find_neighbors(&self) -> (usize, usize) {
+        let s = T::from_usize(self.n_leaves() - 2);
+        self.find_best(|dij, sum_i, sum_j| (dij * s) - sum_i - sum_j)
+    }
+
+    /// Pair with the smallest distance, as used by the agglomerative clustering methods
+    pub fn find_closest(&self) -> (usize, usize) {
+        self.find_best(|dij, _, _| dij)
+    }
+
+    /// Pair (j, i), with j < i, of the best candidate for the criterion q(d(i, j), S_i, S_j).
+    /// Rows are searched in parallel, and the best candidates of the rows are compared as
+    /// Candidates, so the result is the same as a serial search whatever the number of threads.
+    fn find_best<F>(&self, q: F) -> (usize, usize)
+    where
+        F: Fn(T, T, T) -> T + Sync,
+    {
+        (1..self.n_leaves())
+            .into_par_iter()
+            .fold(
+                || None,
+                |best: Option<(Candidate<T>, (usize, usize))>, i| {
+                    let row = self.matrix.row(i);
+                    let sums = &self.sum_cols[..i];
+                    let sum_i = self.sum_cols[i];
+                    let min = row_minimum(row, sums, |dij, sum_j| q(dij, sum_i, sum_j));
+                    // Only the rows that may hold a better candidate are searched again
+                    if best.is_some_and(|(best, _)| min > best.q) {
+                        return best;
+                    }
+                    let mut best = best;
+                    for (j, (dij, sum_j)) in row.iter().zip(sums).enumerate() {
+                        let value = q(*dij, sum_i, *sum_j);
+                        if value <= min {
+                            let candidate = Candidate::new(value, self.ids[i], self.ids[j]);
+                            if best.is_none_or(|(best, _)| candidate.is_better_than(&best)) {
+                                best = Some((candidate, (j, i)));
+                            }
+                        }
+                    }
+                    best
+                },
+            )
+            .reduce(
+                || None,
+                |a, b| match (a, b) {
+                    (Some(a), Some(b)) => Some(if b.0.is_better_than(&a.0) { b } else { a }),
+                    (a, b) => a.or(b),
+                },
+            )
+            .map_or((0, 1), |(_, pair)| pair)
     }
 
     pub fn update_distance_matrix(&mut self, i: usize, j: usize) {
+        let dij = self.matrix.get(i, j);
+        let two = T::from_f64(2.0);
+        self.update_with(i, j, |dik, djk| (dik + djk - dij) / two);
+    }
+
+    /// Merge i and j into a new node u, with d(u, k) = new_distance(d(i, k), d(j, k))
+    pub fn update_with<F>(&mut self, i: usize, j: usize, new_distance: F)
+    where
+        F: Fn(T, T) -> T + Sync,
+    {
         let matrix = &mut self.matrix;
         let sum_cols = &mut self.sum_cols;
-        let dij = matrix[i][j];
-        let n = matrix.len();
+        let n = matrix.size();
         // Remove the ith and jth value to each row
-        for (k, col) in sum_cols.iter_mut().enumerate() {
-            *col -= matrix[i][k] + matrix[j][k];
-        }
-        // Swap rows
-        if j == n - 2 {
+        let distances = &*matrix;
+        sum_cols
+            .par_iter_mut()
+            .with_min_len(MIN_UPDATE_LEN)
+            .enumerate()
+            .for_each(|(k, col)| *col -= distances.get(i, k) + distances.get(j, k));
+        // Swap taxa, and remember where i and j end up
+        let i_is_last = j == n - 2;
+        if i_is_last {
             matrix.swap(i, n - 1);
             sum_cols.swap(i, n - 1);
-            for row in matrix.iter_mut() {
-                row.swap(i, n - 1);
-            }
         } else {
             matrix.swap(i, n - 2);
             matrix.swap(j, n - 1);
             sum_cols.swap(i, n - 2);
             sum_cols.swap(j, n - 1);
-            for row in matrix.iter_mut() {
-                row.swap(i, n - 2);
-                row.swap(j, n - 1);
-            }
         }
-        // Update the row.len() - 2 row (aka u row)
-        for k in 0..matrix.len() - 2 {
-            matrix[n - 2][k] = (matrix[n - 2][k] + matrix[n - 1][k] - dij) / 2.0;
-            matrix[k][n - 2] = matrix[n - 2][k];
-        }
-        // Remove the last row and every last column
+        // Update the n - 2 row (aka u row), from itself and the first n - 2 values of the last one
+        let (u_row, last_row) = matrix.last_two_rows_mut();
+        u_row
+            .par_iter_mut()
+            .with_min_len(MIN_UPDATE_LEN)
+            .zip(last_row)
+            .for_each(|(value, last)| {
+                *value = if i_is_last {
+                    new_distance(*last, *value)
+                } else {
+                    new_distance(*value, *last)
+                }
+            });
+        // Remove the last taxon
         matrix.pop();
         sum_cols.pop();
-        for row in matrix.iter_mut() {
-            row.pop();
-        }
         // Update the sum_cols with RS_i = RS'_i - x - y + z
-        for (index, col) in sum_cols.iter_mut().enumerate().take(n - 2) {
-            *col += matrix[n - 2][index];
+        let u_row = matrix.row(n - 2);
+        sum_cols
+            .par_iter_mut()
+            .with_min_len(MIN_UPDATE_LEN)
+            .zip(u_row)
+            .for_each(|(col, value)| *col += *value);
+        // Compute the sum of the last row, serially so it does not depend on the number of threads
+        sum_cols[n - 2] = u_row.iter().copied().sum::<T>();
+        merge_layout(&mut self.ids, i, j, self.next_id);
+        self.next_id += 1;
+    }
+}
+
+/// Smallest q(d(i, j), S_j) of a row, ignoring NaN. It is computed without branches over LANES
+/// independent minimums, which the compiler turns into SIMD instructions.
+fn row_minimum<T: Float>(row: &[T], sums: &[T], q: impl Fn(T, T) -> T) -> T {
+    let min = |a: T, b: T| if b < a { b } else { a };
+    let mut lanes = [T::INFINITY; LANES];
+    let rows = row.chunks_exact(LANES);
+    let rest = rows.remainder().len();
+    for (values, sums) in rows.zip(sums.chunks_exact(LANES)) {
+        for ((lane, dij), sum_j) in lanes.iter_mut().zip(values).zip(sums) {
+            *lane = min(*lane, q(*dij, *sum_j));
+        }
+    }
+    let start = row.len() - rest;
+    let tail = row[start..].iter().zip(&sums[start..]);
+    tail.fold(
+        lanes.into_iter().fold(T::INFINITY, min),
+        |acc, (dij, sum_j)| min(acc, q(*dij, *sum_j)),
+    )
+}
+
+/// Merge the entries i and j of a vector that follows the layout of the QMatrix:
+/// the merged entry goes to n - 2 and the last one is removed
+pub fn merge_layout<T>(values: &mut Vec<T>, i: usize, j: usize, merged: T) {
+    let n = values.len();
+    if j == n - 2 {
+        values.swap(i, n - 1);
+    } else {
+        values.swap(i, n - 2);
+        values.swap(j, n - 1);
+    }
+    values[n - 2] = merged;
+    values.pop();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Serial search over every pair, as done before the rows were searched in parallel
+    fn serial_neighbors(q: &QMatrix) -> (usize, usize) {
+        let n = q.n_leaves();
+        let mut neighbors = (0, 1);
+        let mut best = Candidate::none();
+        for i in 1..n {
+            for j in 0..i {
+                let value = q.distance(i, j) * (n - 2) as f64 - q.sum_col(i) - q.sum_col(j);
+                let candidate = Candidate::new(value, q.ids[i], q.ids[j]);
+                if candidate.is_better_than(&best) {
+                    best = candidate;
+                    neighbors = (j, i);
+                }
+            }
+        }
+        neighbors
+    }
+
+    #[test]
+    fn test_find_neighbors_matches_serial_search() {
+        use rand::{Rng, SeedableRng};
+        let mut rng = rand::rngs::StdRng::seed_from_u64(21);
+        // Rows shorter and longer than LANES, and few distinct values so that many pairs tie
+        for n in [3, 9, 30, 70] {
+            let d = crate::property_tests::symmetric_matrix(n, |_, _| rng.gen_range(1..4) as f64);
+            let mut q = QMatrix::build(d);
+            while q.n_leaves() > 2 {
+                let (i, j) = q.find_neighbors();
+                assert_eq!((i, j), serial_neighbors(&q));
+                q.update_distance_matrix(i, j);
+            }
         }
-        // Compute the sum of the last row
-        sum_cols[n - 2] = matrix[n - 2].iter().sum::<f64>();
+    }
+
+    #[test]
+    fn test_merge_layout() {
+        let mut sizes = vec![1, 1, 1, 1, 1];
+        merge_layout(&mut sizes, 1, 3, 2);
+        assert_eq!(sizes, vec![1, 1, 1, 2]);
+        merge_layout(&mut sizes, 0, 2, 2);
+        assert_eq!(sizes, vec![2, 1, 2]);
+        merge_layout(&mut sizes, 0, 1, 3);
+        merge_layout(&mut sizes, 0, 1, 5);
+        assert_eq!(sizes, vec![5]);
     }
 }
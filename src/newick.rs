@@ -1,23 +1,28 @@
 use fixedbitset::FixedBitSet;
 use petgraph::stable_graph::NodeIndex;
 
-use crate::Tree;
+use crate::{Error, Result, Tree};
 
 fn format_edge_float<'a>(
     t: &Tree,
     node: NodeIndex,
     parent: NodeIndex,
     buffer: &'a mut dtoa::Buffer,
-) -> &'a str {
+) -> Result<&'a str> {
     let e: petgraph::stable_graph::EdgeIndex = t.find_edge(node, parent).expect("Valid edge");
+    let weight = *t.edge_weight(e).expect("Valid edge");
+    if !weight.is_finite() {
+        return Err(Error::Numerical(format!("branch length is {weight}")));
+    }
     // Stop using format! for performance, create a string directly
-    buffer.format_finite(*t.edge_weight(e).expect("Valid edge"))
+    Ok(buffer.format_finite(weight))
 }
 
 /// Convert a `Tree` to a string according to the [Newick](https://en.wikipedia.org/wiki/Newick_format) format
-pub fn to_newick(t: &Tree) -> String {
+pub fn to_newick(t: &Tree) -> Result<String> {
     let mut buffer = dtoa::Buffer::new();
-    let root = root(t).expect("Node with three edges");
+    let root = root(t)
+        .ok_or_else(|| Error::TreeShape("no internal node with two or three edges".to_string()))?;
     let mut visited = FixedBitSet::with_capacity(t.node_count());
     fn inner(
         t: &Tree,
@@ -25,7 +30,7 @@ pub fn to_newick(t: &Tree) -> String {
         node: NodeIndex,
         parent: NodeIndex,
         buffer: &mut dtoa::Buffer,
-    ) -> String {
+    ) -> Result<String> {
         let mut newick = String::new();
         visited.insert(node.index());
         // If leaf
@@ -33,8 +38,8 @@ pub fn to_newick(t: &Tree) -> String {
             // Stop using format! for performance, create a string directly
             let mut output = t[node].to_owned();
             output.push(':');
-            output.push_str(format_edge_float(t, node, parent, buffer));
-            return output;
+            output.push_str(format_edge_float(t, node, parent, buffer)?);
+            return Ok(output);
         }
         // If internal node
         let mut children: Vec<NodeIndex> = t.neighbors(node).collect();
@@ -45,7 +50,7 @@ pub fn to_newick(t: &Tree) -> String {
         newick.push('(');
         for (index, child) in children.into_iter().enumerate() {
             if !visited.contains(child.index()) {
-                let child_newick = inner(t, visited, child, node, buffer);
+                let child_newick = inner(t, visited, child, node, buffer)?;
                 newick.push_str(&child_newick);
                 if index < n_children - 1 {
                     newick.push(',');
@@ -56,24 +61,22 @@ pub fn to_newick(t: &Tree) -> String {
 
         if t.find_edge(node, parent).is_some() {
             newick.push(':');
-            newick.push_str(format_edge_float(t, node, parent, buffer));
+            newick.push_str(format_edge_float(t, node, parent, buffer)?);
         }
-        newick
+        Ok(newick)
     }
-    let mut output = inner(t, &mut visited, root, root, &mut buffer);
+    let mut output = inner(t, &mut visited, root, root, &mut buffer)?;
     output.push(';');
-    output
+    Ok(output)
 }
 
+/// Root of the output: the internal node with two edges of a rooted tree (as built by the
+/// clustering methods), or else the first internal node with three edges
 fn root(t: &Tree) -> Option<NodeIndex> {
-    let mut root = None;
-    for node in t.node_indices() {
-        if t.neighbors(node).count() == 3 {
-            root = Some(node);
-            break;
-        }
-    }
-    root
+    let internal = || t.node_indices().filter(|node| t[*node].is_empty());
+    internal()
+        .find(|node| t.neighbors(*node).count() == 2)
+        .or_else(|| internal().find(|node| t.neighbors(*node).count() == 3))
 }
 
 // If the node has children, create a sub-tree representation
@@ -96,7 +99,7 @@ mod tests {
             (b, internal, 20.2),
             (c, internal, 77757.1),
         ]);
-        let newick = to_newick(graph);
+        let newick = to_newick(graph).unwrap();
         assert_eq!(newick, "(A:100.1,B:20.2,C:77757.1);");
     }
     #[test]
@@ -116,7 +119,15 @@ mod tests {
             (d, internal_v, 0.4),
             (internal_u, internal_v, 0.5),
         ]);
-        let newick = to_newick(graph);
+        let newick = to_newick(graph).unwrap();
         assert_eq!(newick, "(C:0.3,D:0.4,(A:0.1,B:0.2):0.5);");
     }
+    #[test]
+    fn test_tree_without_internal_node() {
+        let graph = &mut petgraph::Graph::<String, f64, petgraph::Undirected>::new_undirected();
+        let a = graph.add_node("A".to_string());
+        let b = graph.add_node("B".to_string());
+        graph.add_edge(a, b, 1.0);
+        assert!(matches!(to_newick(graph), Err(Error::TreeShape(_))));
+    }
 }
@@ -6,3 +6,27 @@ pub mod random_additive_tree;
 mod tests;
 /// This module contains distance metrics for trees. Branch score-distance and Robinson Foulds are implemented.
 pub mod tree_distances;
+
+/// Symmetric matrix with a zero diagonal, and the upper triangle given by `distance`
+#[cfg(test)]
+pub(crate) fn symmetric_matrix(
+    n: usize,
+    mut distance: impl FnMut(usize, usize) -> f64,
+) -> crate::DistanceMatrix {
+    let upper: Vec<Vec<f64>> = (0..n)
+        .map(|i| (i + 1..n).map(|j| distance(i, j)).collect())
+        .collect();
+    let matrix = (0..n)
+        .map(|i| {
+            (0..n)
+                .map(|j| match i.cmp(&j) {
+                    std::cmp::Ordering::Less => upper[i][j - i - 1],
+                    std::cmp::Ordering::Greater => upper[j][i - j - 1],
+                    std::cmp::Ordering::Equal => 0.0,
+                })
+                .collect()
+        })
+        .collect();
+    let names = (0..n).map(|i| i.to_string()).collect();
+    crate::DistanceMatrix::build(matrix, names).unwrap()
+}
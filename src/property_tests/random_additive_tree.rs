@@ -62,19 +62,18 @@ pub fn distance_matrix_from_tree(t: Tree) -> DistanceMatrix {
         .node_indices()
         .filter(|node| t.edges(*node).count() == 1)
         .collect();
-    let mut mat = vec![vec![0.0; leaves.len()]; leaves.len()];
+    let mut lower = Vec::with_capacity(leaves.len() * leaves.len() / 2);
     for (i, a) in leaves.iter().enumerate() {
-        for (j, b) in leaves.iter().enumerate() {
-            mat[i][j] =
+        for b in &leaves[..i] {
+            lower.push(
                 petgraph::algo::astar(&t, *a, |finish| finish == *b, |e| *e.weight(), |_| 0.0)
                     .expect("All nodes are connected")
-                    .0;
+                    .0,
+            );
         }
     }
-    DistanceMatrix {
-        names: leaves.iter().map(|node| t[*node].clone()).collect(),
-        matrix: mat,
-    }
+    let names = leaves.iter().map(|node| t[*node].clone()).collect();
+    DistanceMatrix::from_lower_triangle(lower, names).unwrap()
 }
 
 #[cfg(test)]
@@ -1,9 +1,9 @@
 #[cfg(test)]
 fn assert_equal_tree(a: &crate::Tree, b: &crate::Tree) {
     use crate::property_tests::tree_distances::{branch_score, robinson_foulds};
-    assert_eq!(robinson_foulds(&a, &b), 0);
-    assert!(petgraph::algo::is_isomorphic(&a, &b));
-    assert!(branch_score(&a, &b) < f64::EPSILON);
+    assert_eq!(robinson_foulds(a, b).unwrap(), 0);
+    assert!(petgraph::algo::is_isomorphic(a, b));
+    assert!(branch_score(a, b).unwrap() < f64::EPSILON);
 }
 
 #[test]
@@ -17,7 +17,7 @@ fn test_random_additive_binary_trees_naive() {
     for i in 4..20 {
         let original_tree = random_unrooted_binary_tree(i);
         let d = distance_matrix_from_tree(original_tree.clone());
-        let tree = canonical_neighbor_joining(d).unwrap();
+        let tree = canonical_neighbor_joining::<f64>(d).unwrap();
         assert_equal_tree(&original_tree, &tree)
     }
 }
@@ -26,30 +26,290 @@ fn test_random_additive_binary_trees_rapid() {
     use crate::property_tests::random_additive_tree::{
         distance_matrix_from_tree, random_unrooted_binary_tree,
     };
-    use crate::rapid_nj::rapid_nj;
+    use crate::rapid_nj::{rapid_nj, RowStorage};
     for i in 4..20 {
         let original_tree = random_unrooted_binary_tree(i);
         let d = distance_matrix_from_tree(original_tree.clone());
         let chunk_size = rand::random::<usize>() % (i + 1) + 1;
-        let tree = rapid_nj(d, chunk_size).unwrap();
-        assert_equal_tree(&original_tree, &tree)
+        for row_storage in [RowStorage::BTree, RowStorage::SortedArray] {
+            let tree = rapid_nj::<f64>(d.clone(), chunk_size, row_storage).unwrap();
+            assert_equal_tree(&original_tree, &tree)
+        }
     }
 }
 
 #[test]
 fn test_random_additive_binary_trees_mix() {
-    use crate::hybrid_nj::neighbor_joining;
+    use crate::hybrid_nj::{neighbor_joining, Switch, VISIT_COST};
     use crate::property_tests::random_additive_tree::{
         distance_matrix_from_tree, random_unrooted_binary_tree,
     };
+    use crate::rapid_nj::RowStorage;
     for i in (25..100).step_by(25) {
         let original_tree = random_unrooted_binary_tree(i);
         let d: crate::distances::DistanceMatrix = distance_matrix_from_tree(original_tree.clone());
         for _ in 0..5 {
             let naive_steps = rand::random::<usize>() % (i + 1);
             let chunk_size = rand::random::<usize>() % (i + 1) + 1;
-            let tree = neighbor_joining(d.clone(), naive_steps, chunk_size).unwrap();
-            assert_equal_tree(&original_tree, &tree)
+            for row_storage in [RowStorage::BTree, RowStorage::SortedArray] {
+                let tree = neighbor_joining::<f64>(
+                    d.clone(),
+                    Switch::CanonicalIters(naive_steps),
+                    chunk_size,
+                    row_storage,
+                )
+                .unwrap();
+                assert_equal_tree(&original_tree, &tree);
+                let tree = neighbor_joining::<f64>(
+                    d.clone(),
+                    Switch::Adaptive(VISIT_COST),
+                    chunk_size,
+                    row_storage,
+                )
+                .unwrap();
+                assert_equal_tree(&original_tree, &tree)
+            }
+        }
+    }
+}
+
+#[test]
+fn test_random_additive_binary_trees_bionj() {
+    use crate::bionj::bionj;
+    use crate::property_tests::random_additive_tree::{
+        distance_matrix_from_tree, random_unrooted_binary_tree,
+    };
+    for i in 4..20 {
+        let original_tree = random_unrooted_binary_tree(i);
+        let d = distance_matrix_from_tree(original_tree.clone());
+        let tree = bionj(d).unwrap();
+        assert_equal_tree(&original_tree, &tree)
+    }
+}
+
+#[test]
+fn test_random_additive_binary_trees_unj() {
+    use crate::property_tests::random_additive_tree::{
+        distance_matrix_from_tree, random_unrooted_binary_tree,
+    };
+    use crate::unj::unweighted_neighbor_joining;
+    for i in 4..20 {
+        let original_tree = random_unrooted_binary_tree(i);
+        let d = distance_matrix_from_tree(original_tree.clone());
+        let tree = unweighted_neighbor_joining(d).unwrap();
+        assert_equal_tree(&original_tree, &tree)
+    }
+}
+
+#[test]
+fn test_ties_are_independent_of_threads_and_chunk_size() {
+    use crate::{
+        hybrid_nj::{neighbor_joining, Switch},
+        naive_nj::canonical_neighbor_joining,
+        rapid_nj::rapid_nj,
+        to_newick, RowStorage,
+    };
+    use rand::{Rng, SeedableRng};
+    let mut rng = rand::rngs::StdRng::seed_from_u64(15);
+    for n in [6, 17, 40] {
+        // Small integer distances, so many pairs tie on q
+        let d = super::symmetric_matrix(n, |_, _| rng.gen_range(1..4) as f64);
+        let solve = |threads: usize, chunk_size: usize| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            pool.install(|| {
+                let canonical = canonical_neighbor_joining::<f64>(d.clone()).unwrap();
+                let rapid = rapid_nj::<f64>(d.clone(), chunk_size, RowStorage::BTree).unwrap();
+                let hybrid = neighbor_joining::<f64>(
+                    d.clone(),
+                    Switch::CanonicalIters(n / 2),
+                    chunk_size,
+                    RowStorage::BTree,
+                )
+                .unwrap();
+                (
+                    to_newick(&canonical).unwrap(),
+                    to_newick(&rapid).unwrap(),
+                    to_newick(&hybrid).unwrap(),
+                )
+            })
+        };
+        let expected = solve(1, n);
+        for (threads, chunk_size) in [(1, 1), (2, 3), (4, 1), (8, 2), (3, n)] {
+            assert_eq!(solve(threads, chunk_size), expected);
+        }
+    }
+}
+
+#[test]
+fn test_integer_matrices_rapid_equals_canonical() {
+    use crate::{
+        hybrid_nj::{neighbor_joining, Switch, VISIT_COST},
+        naive_nj::canonical_neighbor_joining,
+        rapid_nj::rapid_nj,
+        RowStorage,
+    };
+    use rand::{Rng, SeedableRng};
+    let mut rng = rand::rngs::StdRng::seed_from_u64(16);
+    for n in (4..40).step_by(3) {
+        // From very few distinct values (most entries tie) to SNP-like counts
+        for max_distance in [2, 3, 10, 100] {
+            let d = super::symmetric_matrix(n, |_, _| rng.gen_range(1..=max_distance) as f64);
+            let canonical = canonical_neighbor_joining::<f64>(d.clone()).unwrap();
+            let chunk_size = rng.gen_range(1..=n);
+            for row_storage in [RowStorage::BTree, RowStorage::SortedArray] {
+                let rapid = rapid_nj::<f64>(d.clone(), chunk_size, row_storage).unwrap();
+                assert_equal_tree(&canonical, &rapid);
+            }
+            let naive_steps = rng.gen_range(0..=n);
+            for switch in [
+                Switch::CanonicalIters(naive_steps),
+                Switch::Adaptive(VISIT_COST),
+            ] {
+                for row_storage in [RowStorage::BTree, RowStorage::SortedArray] {
+                    let hybrid =
+                        neighbor_joining::<f64>(d.clone(), switch, chunk_size, row_storage)
+                            .unwrap();
+                    assert_equal_tree(&canonical, &hybrid);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_random_additive_binary_trees_single_precision() {
+    use crate::hybrid_nj::{neighbor_joining, Switch};
+    use crate::naive_nj::canonical_neighbor_joining;
+    use crate::property_tests::random_additive_tree::{
+        distance_matrix_from_tree, random_unrooted_binary_tree,
+    };
+    use crate::property_tests::tree_distances::{branch_score, robinson_foulds};
+    use crate::rapid_nj::{rapid_nj, RowStorage};
+    // Same topology, and branch lengths within single precision of the original ones
+    let assert_close_tree = |a: &crate::Tree, b: &crate::Tree| {
+        assert_eq!(robinson_foulds(a, b).unwrap(), 0);
+        assert!(branch_score(a, b).unwrap() < 0.1);
+    };
+    for i in (4..60).step_by(5) {
+        let original_tree = random_unrooted_binary_tree(i);
+        let d = distance_matrix_from_tree(original_tree.clone());
+        let chunk_size = rand::random::<usize>() % (i + 1) + 1;
+        assert_close_tree(
+            &original_tree,
+            &canonical_neighbor_joining::<f32>(d.clone()).unwrap(),
+        );
+        for row_storage in [RowStorage::BTree, RowStorage::SortedArray] {
+            let tree = rapid_nj::<f32>(d.clone(), chunk_size, row_storage).unwrap();
+            assert_close_tree(&original_tree, &tree);
+        }
+        let naive_steps = rand::random::<usize>() % (i + 1);
+        let tree = neighbor_joining::<f32>(
+            d,
+            Switch::CanonicalIters(naive_steps),
+            chunk_size,
+            RowStorage::SortedArray,
+        )
+        .unwrap();
+        assert_close_tree(&original_tree, &tree);
+    }
+}
+
+#[test]
+fn test_hybrid_canonical_fraction_boundaries() {
+    use crate::property_tests::random_additive_tree::{
+        distance_matrix_from_tree, random_unrooted_binary_tree,
+    };
+    use crate::{to_newick, Canonical, Hybrid, NeighborJoiningSolver, RapidBtrees};
+    for n in [4, 5, 10, 37] {
+        let original_tree = random_unrooted_binary_tree(n);
+        let d = distance_matrix_from_tree(original_tree.clone());
+        let chunk_size = rand::random::<usize>() % n + 1;
+        let hybrid = NeighborJoiningSolver::<Hybrid>::build(d.clone(), chunk_size, 0);
+        let with_fraction = |fraction: f64| {
+            NeighborJoiningSolver::<Hybrid>::build(d.clone(), chunk_size, 0)
+                .set_canonical_percentage(fraction)
+                .unwrap()
+                .solve()
+                .unwrap()
+        };
+        let with_steps = |steps: usize| {
+            NeighborJoiningSolver::<Hybrid>::build(d.clone(), chunk_size, 0)
+                .set_canonical_steps(steps)
+                .solve()
+                .unwrap()
+        };
+        let canonical = NeighborJoiningSolver::<Canonical>::default(d.clone())
+            .solve()
+            .unwrap();
+        let rapid = NeighborJoiningSolver::<RapidBtrees>::build(d.clone(), chunk_size)
+            .solve()
+            .unwrap();
+        let newick = |tree: &crate::Tree| to_newick(tree).unwrap();
+        // At the ends, every iteration is done by a single algorithm
+        assert_eq!(newick(&hybrid.solve().unwrap()), newick(&rapid));
+        assert_eq!(newick(&with_fraction(0.0)), newick(&rapid));
+        assert_eq!(newick(&with_fraction(1.0)), newick(&canonical));
+        for steps in [n - 3, n - 2, usize::MAX] {
+            assert_eq!(newick(&with_steps(steps)), newick(&canonical));
+        }
+        // A single iteration done by either algorithm, and anything in between
+        let one_iteration = 1.0 / (n - 3) as f64;
+        for fraction in [one_iteration, 0.5, 1.0 - one_iteration] {
+            assert_equal_tree(&original_tree, &with_fraction(fraction));
+        }
+        for steps in [1, n - 4] {
+            assert_equal_tree(&original_tree, &with_steps(steps));
+        }
+        for fraction in [-0.01, 1.01, f64::NAN] {
+            let solver = NeighborJoiningSolver::<Hybrid>::build(d.clone(), chunk_size, 0);
+            assert!(matches!(
+                solver.set_canonical_percentage(fraction),
+                Err(crate::Error::InvalidParameter(_))
+            ));
+        }
+    }
+}
+
+#[test]
+fn test_adversarial_matrices_rapid_equals_canonical() {
+    use crate::{naive_nj::canonical_neighbor_joining, rapid_nj::rapid_nj, RowStorage};
+    use rand::{Rng, SeedableRng};
+    let mut rng = rand::rngs::StdRng::seed_from_u64(25);
+    for n in (4..40).step_by(3) {
+        let far = rng.gen_range(0..n);
+        let matrices = [
+            // Negative distances, so the row sums of the merged nodes may grow
+            super::symmetric_matrix(n, |_, _| rng.gen_range(-10..=10) as f64),
+            // Mostly negative distances with a few large positive ones
+            super::symmetric_matrix(n, |_, _| {
+                if rng.gen_bool(0.1) {
+                    rng.gen_range(50..=100) as f64
+                } else {
+                    -rng.gen_range(1..=5) as f64
+                }
+            }),
+            // Duplicated taxa, and a taxon far from the others with the largest row sum
+            super::symmetric_matrix(n, |i, j| {
+                if i == far || j == far {
+                    1000.0
+                } else if i / 2 == j / 2 {
+                    0.0
+                } else {
+                    rng.gen_range(1..=3) as f64
+                }
+            }),
+        ];
+        for d in matrices {
+            let canonical = canonical_neighbor_joining::<f64>(d.clone()).unwrap();
+            for chunk_size in [1, rng.gen_range(1..=n), n] {
+                for row_storage in [RowStorage::BTree, RowStorage::SortedArray] {
+                    let rapid = rapid_nj::<f64>(d.clone(), chunk_size, row_storage).unwrap();
+                    assert_equal_tree(&canonical, &rapid);
+                }
+            }
         }
     }
 }
@@ -4,7 +4,7 @@ use bit_set::BitSet;
 use bit_vec::BitVec;
 use petgraph::stable_graph::EdgeIndex;
 
-use crate::Tree;
+use crate::{Error, Result, Tree};
 
 fn count_leaves(x: &Tree) -> usize {
     let mut leaf_count = 0;
@@ -16,11 +16,9 @@ fn count_leaves(x: &Tree) -> usize {
     leaf_count
 }
 
-/// Calculate the [Branch-Score distance](https://www.cs.mcgill.ca/~birch/birchhomedir/doc/Phylip/treedist.html) between two trees. It takes the branch length into account. 
-pub fn branch_score(a: &Tree, b: &Tree) -> f64 {
-    let n_leaves = (count_leaves(a), count_leaves(b));
-    assert_eq!(n_leaves.0, n_leaves.1);
-    let n_leaves = n_leaves.0;
+/// Calculate the [Branch-Score distance](https://www.cs.mcgill.ca/~birch/birchhomedir/doc/Phylip/treedist.html) between two trees. It takes the branch length into account.
+pub fn branch_score(a: &Tree, b: &Tree) -> Result<f64> {
+    let n_leaves = same_leaf_count(a, b)?;
     let mut bits_a = HashMap::new();
     let mut bits_b = HashMap::new();
     a.edge_indices()
@@ -47,14 +45,12 @@ pub fn branch_score(a: &Tree, b: &Tree) -> f64 {
         let b = bits_b.get(key).unwrap_or(&0.0);
         distance += (a - b).powi(2);
     }
-    distance
+    Ok(distance)
 }
 
-/// Calculate the [Robinson-Foulds](https://en.wikipedia.org/wiki/Robinson%E2%80%93Foulds_metric) distance between two trees. It doesn't take branch length into account. 
-pub fn robinson_foulds(a: &Tree, b: &Tree) -> usize {
-    let n_leaves = (count_leaves(a), count_leaves(b));
-    assert_eq!(n_leaves.0, n_leaves.1);
-    let n_leaves = n_leaves.0;
+/// Calculate the [Robinson-Foulds](https://en.wikipedia.org/wiki/Robinson%E2%80%93Foulds_metric) distance between two trees. It doesn't take branch length into account.
+pub fn robinson_foulds(a: &Tree, b: &Tree) -> Result<usize> {
+    let n_leaves = same_leaf_count(a, b)?;
     let bits_a: HashSet<BitVec> = HashSet::from_iter(
         a.edge_indices()
             .map(|edge| collect_bit_vector(a, edge, n_leaves)),
@@ -75,7 +71,19 @@ pub fn robinson_foulds(a: &Tree, b: &Tree) -> usize {
             distance += 1;
         }
     }
-    distance
+    Ok(distance)
+}
+
+/// Number of leaves of both trees, which must be the same
+fn same_leaf_count(a: &Tree, b: &Tree) -> Result<usize> {
+    let n_leaves = (count_leaves(a), count_leaves(b));
+    if n_leaves.0 != n_leaves.1 {
+        return Err(Error::TreeShape(format!(
+            "trees have {} and {} leaves",
+            n_leaves.0, n_leaves.1
+        )));
+    }
+    Ok(n_leaves.0)
 }
 
 fn collect_bit_vector(
@@ -163,12 +171,12 @@ mod tests {
             t2.add_edge(d, u, 1.0);
             t2.add_edge(v, u, 1.0);
         }
-        assert_eq!(robinson_foulds(&t1, &t2), 2);
-        assert_eq!(robinson_foulds(&t1, &t1), 0);
-        assert_eq!(robinson_foulds(&t2, &t2), 0);
+        assert_eq!(robinson_foulds(&t1, &t2).unwrap(), 2);
+        assert_eq!(robinson_foulds(&t1, &t1).unwrap(), 0);
+        assert_eq!(robinson_foulds(&t2, &t2).unwrap(), 0);
         //
-        assert_eq!(branch_score(&t1, &t2), 112.0);
-        assert_eq!(branch_score(&t1, &t1), 0.0);
-        assert_eq!(branch_score(&t2, &t2), 0.0);
+        assert_eq!(branch_score(&t1, &t2).unwrap(), 112.0);
+        assert_eq!(branch_score(&t1, &t1).unwrap(), 0.0);
+        assert_eq!(branch_score(&t2, &t2).unwrap(), 0.0);
     }
 }
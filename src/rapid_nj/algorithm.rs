@@ -1,11 +1,15 @@
-use crate::{distances::DistanceMatrix, ResultBox, Tree};
+use crate::{distances::DistanceMatrix, float::Float, Result, Tree};
 
-use super::{phylo_tree::PhyloTree, qmatrix::QMatrix};
+use super::{phylo_tree::PhyloTree, qmatrix::QMatrix, RowStorage};
 
-pub fn rapid_nj(dist: DistanceMatrix, chunk_size: usize) -> ResultBox<Tree> {
-    let mut q = QMatrix::from(&dist);
-    q.set_chunk_size(chunk_size);
+pub fn rapid_nj<T: Float>(
+    dist: DistanceMatrix,
+    chunk_size: usize,
+    row_storage: RowStorage,
+) -> Result<Tree> {
     let mut t = PhyloTree::build(&dist.names);
+    let mut q = QMatrix::<T>::with_rows(dist, row_storage);
+    q.set_chunk_size(chunk_size);
     while q.n_leaves() > 3 {
         // Find the minimum element in the distance matrix
         let (i, j) = q.find_neighbors();
@@ -16,21 +20,22 @@ pub fn rapid_nj(dist: DistanceMatrix, chunk_size: usize) -> ResultBox<Tree> {
     Ok(terminate_nj(t, q))
 }
 
-fn terminate_nj(tree: PhyloTree, q: QMatrix) -> Tree {
+fn terminate_nj<T: Float>(tree: PhyloTree, q: QMatrix<T>) -> Tree {
     // Unmerged nodes are those that are not None in q.trees
     let unmerged = q.unmerged_nodes();
     let (i, j, m) = (unmerged[0], unmerged[1], unmerged[2]);
 
-    let dvi = (q.distance(i, j) + q.distance(i, m) - q.distance(j, m)) / 2.0;
-    let dvj = (q.distance(i, j) + q.distance(j, m) - q.distance(i, m)) / 2.0;
-    let dvm = (q.distance(i, m) + q.distance(j, m) - q.distance(i, j)) / 2.0;
+    let two = T::from_f64(2.0);
+    let dvi = (q.distance(i, j) + q.distance(i, m) - q.distance(j, m)) / two;
+    let dvj = (q.distance(i, j) + q.distance(j, m) - q.distance(i, m)) / two;
+    let dvm = (q.distance(i, m) + q.distance(j, m) - q.distance(i, j)) / two;
 
     let (i, j, m) = (tree.nodes[&i], tree.nodes[&j], tree.nodes[&m]);
     let mut tree = tree.tree;
     let v = tree.add_node("".to_owned());
-    tree.add_edge(v, i, dvi);
-    tree.add_edge(v, j, dvj);
-    tree.add_edge(v, m, dvm);
+    tree.add_edge(v, i, dvi.to_branch_length());
+    tree.add_edge(v, j, dvj.to_branch_length());
+    tree.add_edge(v, m, dvm.to_branch_length());
 
     tree
 }
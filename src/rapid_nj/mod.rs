@@ -2,17 +2,19 @@ mod algorithm;
 mod node;
 mod phylo_tree;
 mod qmatrix;
+mod rows;
 pub use algorithm::rapid_nj;
 pub(crate) use phylo_tree::PhyloTree;
 pub(crate) use qmatrix::QMatrix;
+pub use rows::RowStorage;
 
-pub(crate) struct DataRapidNJ {
-    pub qmatrix: QMatrix,
+pub(crate) struct DataRapidNJ<T> {
+    pub qmatrix: QMatrix<T>,
     pub phylo_tree: phylo_tree::PhyloTree,
 }
 
-impl DataRapidNJ {
-    pub fn new(qmatrix: QMatrix, phylo_tree: phylo_tree::PhyloTree) -> Self {
+impl<T> DataRapidNJ<T> {
+    pub fn new(qmatrix: QMatrix<T>, phylo_tree: phylo_tree::PhyloTree) -> Self {
         Self {
             qmatrix,
             phylo_tree,
@@ -1,30 +1,72 @@
+use std::cmp::Ordering;
+
+use crate::float::Float;
+
 #[derive(Debug, Clone)]
-pub struct Node {
+pub struct Node<T = f64> {
     pub index: usize,
-    pub value: f64,
+    pub value: T,
 }
 
-impl Node {
-    pub fn new(index: usize, value: f64) -> Self {
+impl<T> Node<T> {
+    pub fn new(index: usize, value: T) -> Self {
         Self { index, value }
     }
 }
-// Define comparison for Node, so only the value is compared
-impl PartialEq for Node {
+// Nodes are ordered by value and then by index, so a row can hold several columns with the same
+// distance and removing a node never removes another column. NaN values go last.
+impl<T: Float> PartialEq for Node<T> {
     fn eq(&self, other: &Self) -> bool {
-        self.value == other.value
+        self.cmp(other) == Ordering::Equal
     }
 }
-impl Eq for Node {}
+impl<T: Float> Eq for Node<T> {}
 
-impl PartialOrd for Node {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+impl<T: Float> PartialOrd for Node<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
     }
 }
 
-impl Ord for Node {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.value.partial_cmp(&other.value).unwrap()
+impl<T: Float> Ord for Node<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let value = match (self.value.is_nan(), other.value.is_nan()) {
+            (false, false) => self.value.total_cmp(&other.value),
+            (a, b) => a.cmp(&b),
+        };
+        value.then_with(|| self.index.cmp(&other.index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn test_tied_values() {
+        let mut row = BTreeSet::new();
+        assert!(row.insert(Node::new(3, 1.0)));
+        assert!(row.insert(Node::new(1, 1.0)));
+        assert!(row.insert(Node::new(2, 0.5)));
+        assert!(!row.insert(Node::new(1, 1.0)));
+        let indexes: Vec<usize> = row.iter().map(|node| node.index).collect();
+        assert_eq!(indexes, vec![2, 1, 3]);
+        // Only the given column is removed
+        assert!(row.remove(&Node::new(3, 1.0)));
+        let indexes: Vec<usize> = row.iter().map(|node| node.index).collect();
+        assert_eq!(indexes, vec![2, 1]);
+    }
+
+    #[test]
+    fn test_nan_goes_last() {
+        let mut row = BTreeSet::new();
+        row.insert(Node::new(0, -f64::NAN));
+        row.insert(Node::new(1, f64::NAN));
+        row.insert(Node::new(2, f64::INFINITY));
+        row.insert(Node::new(3, -1.0));
+        let indexes: Vec<usize> = row.iter().map(|node| node.index).collect();
+        assert_eq!(indexes, vec![3, 2, 0, 1]);
+        assert!(row.remove(&Node::new(1, f64::NAN)));
     }
 }
@@ -2,6 +2,8 @@ use std::collections::HashMap;
 
 use petgraph::{graph::UnGraph, stable_graph::NodeIndex};
 
+use crate::float::Float;
+
 #[derive(Debug, Clone)]
 pub(crate) struct PhyloTree {
     pub tree: crate::Tree,
@@ -26,7 +28,7 @@ impl PhyloTree {
         }
     }
 
-    pub fn merge_neighbors(&mut self, a: usize, b: usize, dau: f64, dbu: f64) -> NodeIndex {
+    pub fn merge_neighbors<T: Float>(&mut self, a: usize, b: usize, dau: T, dbu: T) -> NodeIndex {
         // Get nodes to merge
         let a_node = self.nodes[&a];
         let b_node = self.nodes[&b];
@@ -36,8 +38,8 @@ impl PhyloTree {
         self.n_nodes += 1;
 
         // Add new edges
-        self.tree.add_edge(u, a_node, dau);
-        self.tree.add_edge(u, b_node, dbu);
+        self.tree.add_edge(u, a_node, dau.to_branch_length());
+        self.tree.add_edge(u, b_node, dbu.to_branch_length());
         u
     }
 }
@@ -1,146 +1,182 @@
+use crate::candidate::Candidate;
 use crate::distances::DistanceMatrix;
-use crate::rapid_nj::node::Node;
+use crate::float::Float;
+use crate::rapid_nj::rows::{RowStorage, Rows};
 use parking_lot::RwLock;
 use rayon::prelude::*;
 use std::cmp::Ordering;
-use std::collections::BTreeSet;
 
-pub struct QMatrix {
-    pub distances: Vec<Option<Vec<f64>>>,
-    pub sum_cols: Vec<Option<f64>>,
+pub struct QMatrix<T = f64> {
+    pub distances: Vec<Option<Vec<T>>>,
+    pub sum_cols: Vec<Option<T>>,
+    /// Nodes by decreasing row sum, the merged ones last
     indexes: Vec<usize>,
-    trees: Vec<Option<BTreeSet<Node>>>,
-    u_max: f64,
+    rows: Rows<T>,
+    /// Largest row sum of the nodes left, which bounds the q values of every row
+    u_max: T,
     n: usize,
     n_leaves: usize,
     chunk_size: usize,
 }
 
-impl QMatrix {
-    pub fn distance(&self, i: usize, j: usize) -> f64 {
+impl<T: Float> QMatrix<T> {
+    pub fn distance(&self, i: usize, j: usize) -> T {
         Self::distances_vec(&self.distances, i, j)
     }
-    pub fn distances_vec(distances: &[Option<Vec<f64>>], i: usize, j: usize) -> f64 {
+    pub fn distances_vec(distances: &[Option<Vec<T>>], i: usize, j: usize) -> T {
         match i.cmp(&j) {
             Ordering::Less => distances[i].as_ref().unwrap()[j - i - 1],
             Ordering::Greater => distances[j].as_ref().unwrap()[i - j - 1],
-            Ordering::Equal => 0.0,
+            Ordering::Equal => T::ZERO,
         }
     }
 
     pub fn find_neighbors(&self) -> (usize, usize) {
-        // Create slices of the self.trees
-        let mut qmin_shared = f64::INFINITY; // Shared q_min
-        let mut min_index_shared = (0, 0); // Shared min_index
-                                           //  first entry in each row can be searched for a good minimu
+        self.find_best().pair
+    }
+
+    /// Closest neighbors and the number of row entries a search needs to prove them the closest:
+    /// the entries of every row up to the first one whose bound is above the best q. The entries
+    /// actually visited depend on when the chunks share their best candidates, so they vary with
+    /// the scheduling of the threads, whereas this count only depends on the matrix.
+    pub fn find_neighbors_counting(&self) -> ((usize, usize), usize) {
+        let best = self.find_best();
+        let s = T::from_usize(self.n_leaves - 2);
+        let visited = self
+            .indexes
+            .par_chunks(self.chunk_size)
+            .map(|indexes| {
+                let mut visited = 0;
+                for i in indexes.iter() {
+                    if let Some(row) = self.rows.row(*i, &self.sum_cols) {
+                        let sum_i = self.sum_cols[*i].expect("Valid index");
+                        for (_, distance) in row {
+                            visited += 1;
+                            if s * distance - sum_i - self.u_max > best.q {
+                                break;
+                            }
+                        }
+                    }
+                }
+                visited
+            })
+            .sum();
+        (best.pair, visited)
+    }
+
+    fn find_best(&self) -> Candidate<T> {
+        //  first entry in each row can be searched for a good minimum
+        let mut best_shared = Candidate::none();
+        let s = T::from_usize(self.n_leaves - 2);
         self.indexes.iter().for_each(|i| {
-            if let Some(tree) = &self.trees[*i] {
-                if let Some(first) = tree.first() {
-                    let j = first.index;
-                    let q = (self.n_leaves as f64 - 2.0) * self.distance(*i, j)
+            if let Some(mut row) = self.rows.row(*i, &self.sum_cols) {
+                if let Some((j, _)) = row.next() {
+                    let q = s * self.distance(*i, j)
                         - self.sum_cols[*i].expect("Valid index")
                         - self.sum_cols[j].expect("Valid index");
-                    if q < qmin_shared {
-                        qmin_shared = q;
-                        min_index_shared = (*i, j);
+                    let candidate = Candidate::new(q, *i, j);
+                    if candidate.is_better_than(&best_shared) {
+                        best_shared = candidate;
                     }
                 }
             }
         });
-        let qmin_shared = RwLock::new(qmin_shared);
-        let min_index_shared = RwLock::new(min_index_shared);
+        // Every chunk shares its best candidate, so the others can prune their search sooner.
+        // Ties are broken by index, so the result does not depend on the order of the chunks.
+        let best_shared = RwLock::new(best_shared);
         let chunk_size = self.chunk_size;
         self.indexes.par_chunks(chunk_size).for_each(|indexes| {
-            let mut qmin;
-            let mut min_index = (0, 0);
-            {
-                let qmin_shared = qmin_shared.read();
-                qmin = *qmin_shared;
-            }
+            let mut best = *best_shared.read();
             // While let some tree
             for i in indexes.iter() {
-                if let Some(tree) = &self.trees[*i] {
-                    for node in tree.iter() {
-                        let j = node.index;
-                        if (self.n_leaves as f64 - 2.0) * self.distance(*i, j)
-                            - self.sum_cols[*i].expect("Valid index")
-                            - self.u_max
-                            >= qmin
+                if let Some(row) = self.rows.row(*i, &self.sum_cols) {
+                    for (j, distance) in row {
+                        // Strictly greater, as a tie may still win by index
+                        if s * distance - self.sum_cols[*i].expect("Valid index") - self.u_max
+                            > best.q
                         {
                             break;
                         }
-                        let q = (self.n_leaves as f64 - 2.0) * self.distance(*i, j)
+                        let q = s * self.distance(*i, j)
                             - self.sum_cols[*i].expect("Valid index")
                             - self.sum_cols[j].expect("Valid index");
-                        if q < qmin {
-                            qmin = q;
-                            min_index = (*i, j);
+                        let candidate = Candidate::new(q, *i, j);
+                        if candidate.is_better_than(&best) {
+                            best = candidate;
                         }
                     }
                 }
-                if qmin < *qmin_shared.read() {
-                    let mut qmin_write = qmin_shared.write();
-                    let mut min_index_write = min_index_shared.write();
-                    if qmin < *qmin_write {
-                        *qmin_write = qmin;
-                        *min_index_write = min_index;
+                if best.is_better_than(&best_shared.read()) {
+                    let mut best_write = best_shared.write();
+                    if best.is_better_than(&best_write) {
+                        *best_write = best;
+                    } else {
+                        best = *best_write;
                     }
                 }
             }
         });
-        // Choose the minimum of the minima
-        min_index_shared.into_inner()
+        best_shared.into_inner()
     }
     pub fn update(&mut self, i: usize, j: usize) {
-        self.trees[i] = None;
-        self.trees[j] = None;
         self.sum_cols[i] = None;
         self.sum_cols[j] = None;
         let distances = &mut self.distances;
-        self.sum_cols.push(Some(0.0));
-        for (m, row) in self.trees.iter_mut().enumerate() {
-            if row.is_none() {
+        let dij = Self::distances_vec(distances, i, j);
+        let mut new_distances = Vec::with_capacity(self.n_leaves - 2);
+        let mut sum_new = T::ZERO;
+        for m in 0..self.n {
+            if self.sum_cols[m].is_none() {
                 continue;
             }
-            let row = row.as_mut().unwrap();
-
             let dim = Self::distances_vec(distances, i, m);
-            row.remove(&Node::new(i, dim));
             let djm = Self::distances_vec(distances, j, m);
-            row.remove(&Node::new(j, djm));
-
-            let new_distance = 0.5
-                * (Self::distances_vec(distances, i, m) + Self::distances_vec(distances, j, m)
-                    - Self::distances_vec(distances, i, j));
-            row.insert(Node::new(self.n, new_distance));
-
+            let new_distance = T::from_f64(0.5) * (dim + djm - dij);
             self.sum_cols[m] =
                 Some(self.sum_cols[m].expect("Valid index") - dim - djm + new_distance);
-            self.sum_cols[self.n] =
-                Some(self.sum_cols[self.n].expect("Valid index") + new_distance);
+            sum_new += new_distance;
             distances[m].as_mut().unwrap().push(new_distance);
+            new_distances.push((m, new_distance));
         }
+        self.sum_cols.push(Some(sum_new));
         self.n_leaves -= 1;
         self.n += 1;
+        self.distances.push(Some(Vec::with_capacity(self.n_leaves)));
+        let distances = &self.distances;
+        // The rows of i and j are still there, so their old distances can be found
+        self.rows.merge(
+            i,
+            j,
+            |m| {
+                (
+                    Self::distances_vec(distances, i, m),
+                    Self::distances_vec(distances, j, m),
+                )
+            },
+            &new_distances,
+            self.n_leaves,
+            &self.sum_cols,
+        );
         self.distances[i] = None;
         self.distances[j] = None;
-        self.trees.push(Some(BTreeSet::new()));
-        self.distances.push(Some(Vec::with_capacity(self.n_leaves)));
 
         self.indexes.push(self.n - 1);
-        self.indexes.par_sort_unstable_by(|a, b| {
-            self.sum_cols[*b].partial_cmp(&self.sum_cols[*a]).unwrap()
-        });
+        let sum_cols = &self.sum_cols;
+        self.indexes
+            .par_sort_unstable_by(|a, b| compare_sums(sum_cols[*b], sum_cols[*a]));
+        // The sums change at every merge, and may grow when there are negative distances
+        self.u_max = max_sum(&self.indexes, &self.sum_cols);
     }
 
     pub fn n_leaves(&self) -> usize {
         self.n_leaves
     }
-    pub fn new_node_distances(&self, i: usize, j: usize) -> (f64, f64) {
-        let s = (self.n_leaves() - 2) as f64;
+    pub fn new_node_distances(&self, i: usize, j: usize) -> (T, T) {
+        let s = T::from_usize(self.n_leaves() - 2);
+        let two = T::from_f64(2.0);
         let dist_ui = self.distance(i, j) + self.sum_cols[i].expect("Valid index") / s
             - self.sum_cols[j].expect("Valid index") / s;
-        (dist_ui / 2.0, self.distance(i, j) - dist_ui / 2.0)
+        (dist_ui / two, self.distance(i, j) - dist_ui / two)
     }
     pub fn unmerged_nodes(&self) -> Vec<usize> {
         // Get index of all Some valyes in self.trees
@@ -158,46 +194,58 @@ impl QMatrix {
     }
 }
 
+/// Total order of row sums, merged rows (None) go first
+fn compare_sums<T: Float>(a: Option<T>, b: Option<T>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => a.total_cmp(&b),
+        (a, b) => a.is_some().cmp(&b.is_some()),
+    }
+}
+
+/// Row sum of the first node of the indexes sorted by decreasing sum
+fn max_sum<T: Float>(indexes: &[usize], sum_cols: &[Option<T>]) -> T {
+    sum_cols[indexes[0]].expect("Nodes left")
+}
+
 // Implement from DistanceMatrix
 impl From<&DistanceMatrix> for QMatrix {
     fn from(d: &DistanceMatrix) -> Self {
+        Self::with_rows(d.clone(), RowStorage::default())
+    }
+}
+
+impl<T: Float> QMatrix<T> {
+    /// Build from a distance matrix, with the given storage of the sorted rows
+    pub fn with_rows(d: DistanceMatrix, storage: RowStorage) -> Self {
         let n = d.size();
         let n_leaves = n;
-        let matrix = &d.matrix;
-        let sum_cols: Vec<Option<f64>> = matrix
-            .par_iter()
-            .map(|row| Some(row.iter().sum::<f64>()))
-            .collect();
-        let u_max = sum_cols
-            .par_iter()
-            .max_by(|a, b| a.unwrap().partial_cmp(&b.unwrap()).unwrap())
-            .unwrap()
-            .unwrap();
-        let mut distances = Vec::with_capacity(n);
-        for (i, whole_row) in matrix.iter().enumerate() {
-            let mut row = Vec::with_capacity(n - i - 1);
-            for distance in whole_row.iter().skip(i + 1) {
-                row.push(*distance);
-            }
-            distances.push(Some(row));
-        }
-        let mut trees = Vec::with_capacity(n);
-        for (row_index, row) in distances.iter().enumerate() {
-            let mut tree = BTreeSet::new();
-            for (col_index, value) in row.as_ref().unwrap().iter().enumerate() {
-                tree.insert(Node::new(col_index + row_index + 1, *value));
+        let triangle = d.into_triangle::<T>();
+        // Every row is summed in the order of the columns
+        let mut sums = vec![T::ZERO; n];
+        for i in 0..n {
+            for (j, value) in triangle.row(i).iter().enumerate() {
+                sums[i] += *value;
+                sums[j] += *value;
             }
-            trees.push(Some(tree));
         }
+        let sum_cols: Vec<Option<T>> = sums.into_iter().map(Some).collect();
+        // Row i holds the distances to the nodes after it, the ith column of the lower triangle
+        let distances: Vec<Option<Vec<T>>> = (0..n)
+            .into_par_iter()
+            .map(|i| Some((i + 1..n).map(|k| triangle.get(k, i)).collect()))
+            .collect();
+        drop(triangle);
+        let rows = Rows::build(&distances, storage);
         let mut indexes = (0..n).collect::<Vec<usize>>();
         indexes.reserve_exact(n);
-        indexes.par_sort_unstable_by(|a, b| sum_cols[*b].partial_cmp(&sum_cols[*a]).unwrap());
+        indexes.par_sort_unstable_by(|a, b| compare_sums(sum_cols[*b], sum_cols[*a]));
+        let u_max = max_sum(&indexes, &sum_cols);
 
         let chunk_size = 1;
         QMatrix {
             distances,
             sum_cols,
-            trees,
+            rows,
             indexes,
             u_max,
             n,
@@ -211,7 +259,7 @@ impl From<&DistanceMatrix> for QMatrix {
 #[cfg(test)]
 mod tests {
     use super::QMatrix;
-    use crate::{distances::DistanceMatrix, rapid_nj::qmatrix::Node};
+    use crate::distances::DistanceMatrix;
     #[test]
     fn test_from_distance_matrix() {
         let d = wikipedia_distance_matrix();
@@ -222,6 +270,7 @@ mod tests {
             q.sum_cols,
             vec![Some(31.0), Some(34.0), Some(34.0), Some(30.0), Some(27.0)]
         );
+        assert_eq!(q.u_max, 34.0);
         // Check only the upper triangle is stored
         assert_eq!(
             &q.distances,
@@ -235,19 +284,13 @@ mod tests {
         );
         for i in 0..5 {
             for j in 0..5 {
-                assert_eq!(q.distance(i, j), d.matrix[i][j]);
+                assert_eq!(q.distance(i, j), d.get(i, j));
             }
         }
         // Check tree one should be Node(1, 5.0), Node(2, 9.0), Node(3, 9.0)
-        let expected_one = vec![
-            Node::new(1, 5.0),
-            Node::new(4, 8.0),
-            Node::new(2, 9.0),
-            Node::new(3, 9.0),
-        ];
-        for (node, expected) in q.trees[0].as_ref().unwrap().iter().zip(expected_one.iter()) {
-            assert_eq!(node, expected);
-        }
+        let expected_one = vec![(1, 5.0), (4, 8.0), (2, 9.0), (3, 9.0)];
+        let row: Vec<(usize, f64)> = q.rows.row(0, &q.sum_cols).unwrap().collect();
+        assert_eq!(row, expected_one);
     }
     #[test]
     fn test_find_neighbors() {
@@ -276,7 +319,9 @@ mod tests {
             q.sum_cols,
             vec![None, None, Some(22.0), Some(18.0), Some(16.0), Some(20.0)]
         );
-        assert_eq!(q.find_neighbors(), (3, 4));
+        assert_eq!(q.u_max, 22.0);
+        // (2, 5) and (3, 4) tie on q, and the smallest pair wins
+        assert_eq!(q.find_neighbors(), (2, 5));
         q.update(3, 4);
         assert_eq!(
             &q.distances,
@@ -293,21 +338,22 @@ mod tests {
     }
 
     fn wikipedia_distance_matrix() -> DistanceMatrix {
-        DistanceMatrix {
-            matrix: vec![
+        DistanceMatrix::build(
+            vec![
                 vec![0.0, 5.0, 9.0, 9.0, 8.0],
                 vec![5.0, 0.0, 10.0, 10.0, 9.0],
                 vec![9.0, 10.0, 0.0, 8.0, 7.0],
                 vec![9.0, 10.0, 8.0, 0.0, 3.0],
                 vec![8.0, 9.0, 7.0, 3.0, 0.0],
             ],
-            names: vec![
+            vec![
                 "A".to_string(),
                 "B".to_string(),
                 "C".to_string(),
                 "D".to_string(),
                 "E".to_string(),
             ],
-        }
+        )
+        .unwrap()
     }
 }
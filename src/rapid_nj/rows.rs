@@ -0,0 +1,226 @@
+use std::cmp::Ordering;
+use std::collections::{btree_set, BTreeSet};
+
+use rayon::prelude::*;
+
+use crate::{float::Float, rapid_nj::node::Node};
+
+/// How the rows of the RapidBtrees solver are kept sorted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RowStorage {
+    /// A B-tree per row, updated on every merge (the fastest option)
+    #[default]
+    BTree,
+    /// A sorted array per row, as in the RapidNJ paper. Rows are sorted once, merged columns are
+    /// skipped when found and removed every time the number of leaves halves. Each entry takes
+    /// 8 bytes (a single-precision lower bound of the distance and a 32-bit index), around a third of a B-tree entry.
+    SortedArray,
+}
+
+/// Entry of a sorted array: the distance, rounded down to single precision, and the column
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Entry {
+    value: f32,
+    index: u32,
+}
+
+impl Entry {
+    pub fn new<T: Float>(index: usize, value: T) -> Self {
+        let value = value.to_f64();
+        let mut rounded = value as f32;
+        if rounded as f64 > value {
+            rounded = rounded.next_down();
+        }
+        Entry {
+            value: rounded,
+            index: index as u32,
+        }
+    }
+    // Same order as Node: by value, NaN last, and then by index
+    fn order(a: &Self, b: &Self) -> Ordering {
+        let value = match (a.value.is_nan(), b.value.is_nan()) {
+            (false, false) => a.value.total_cmp(&b.value),
+            (a, b) => a.cmp(&b),
+        };
+        value.then_with(|| a.index.cmp(&b.index))
+    }
+}
+
+/// Rows of the QMatrix, sorted by distance.
+///
+/// B-tree rows hold the columns created after them and get every new node inserted. Sorted arrays
+/// are never updated: every new node gets a row with the columns that are still unmerged. Either
+/// way, each pair of nodes is found in exactly one row. Sorted arrays are kept in decreasing order,
+/// so the merged columns at the front of a row can be popped.
+pub(crate) enum Rows<T> {
+    BTree(Vec<Option<BTreeSet<Node<T>>>>),
+    SortedArray {
+        rows: Vec<Option<Vec<Entry>>>,
+        // Number of leaves when the merged columns were last removed
+        compacted_at: usize,
+    },
+}
+
+impl<T: Float> Rows<T> {
+    /// Sorted rows from the upper triangle of a distance matrix
+    pub fn build(distances: &[Option<Vec<T>>], storage: RowStorage) -> Self {
+        match storage {
+            RowStorage::BTree => Rows::BTree(
+                distances
+                    .iter()
+                    .enumerate()
+                    .map(|(i, row)| {
+                        let row = row.as_ref().expect("Valid row");
+                        Some(
+                            row.iter()
+                                .enumerate()
+                                .map(|(k, value)| Node::new(i + k + 1, *value))
+                                .collect(),
+                        )
+                    })
+                    .collect(),
+            ),
+            RowStorage::SortedArray => {
+                let n = distances.len();
+                let rows = (0..n)
+                    .into_par_iter()
+                    .map(|i| {
+                        let mut row: Vec<Entry> = (0..i)
+                            .map(|j| {
+                                let distance = distances[j].as_ref().expect("Valid row")[i - j - 1];
+                                Entry::new(j, distance)
+                            })
+                            .collect();
+                        row.sort_unstable_by(|a, b| Entry::order(b, a));
+                        Some(row)
+                    })
+                    .collect();
+                Rows::SortedArray {
+                    rows,
+                    compacted_at: n,
+                }
+            }
+        }
+    }
+
+    /// Unmerged columns of row i, with a lower bound of their distance, in increasing order.
+    /// `alive` tells which nodes are not merged yet.
+    pub fn row<'a>(&'a self, i: usize, alive: &'a [Option<T>]) -> Option<RowIter<'a, T>> {
+        match self {
+            Rows::BTree(trees) => trees[i].as_ref().map(|tree| RowIter::BTree(tree.iter())),
+            Rows::SortedArray { rows, .. } => rows[i]
+                .as_ref()
+                .map(|row| RowIter::SortedArray(row.iter().rev(), alive)),
+        }
+    }
+
+    /// Merge i and j into a new node, whose row goes last. `new_distances` holds the distance
+    /// from the new node to every unmerged m, and `old_distances(m)` gives d(i, m) and d(j, m).
+    pub fn merge(
+        &mut self,
+        i: usize,
+        j: usize,
+        old_distances: impl Fn(usize) -> (T, T),
+        new_distances: &[(usize, T)],
+        n_leaves: usize,
+        alive: &[Option<T>],
+    ) {
+        match self {
+            Rows::BTree(trees) => {
+                let u = trees.len();
+                trees[i] = None;
+                trees[j] = None;
+                for (m, new_distance) in new_distances {
+                    let row = trees[*m].as_mut().expect("Valid row");
+                    let (dim, djm) = old_distances(*m);
+                    row.remove(&Node::new(i, dim));
+                    row.remove(&Node::new(j, djm));
+                    row.insert(Node::new(u, *new_distance));
+                }
+                trees.push(Some(BTreeSet::new()));
+            }
+            Rows::SortedArray { rows, compacted_at } => {
+                rows[i] = None;
+                rows[j] = None;
+                let mut row: Vec<Entry> = new_distances
+                    .iter()
+                    .map(|(m, distance)| Entry::new(*m, *distance))
+                    .collect();
+                row.sort_unstable_by(|a, b| Entry::order(b, a));
+                rows.push(Some(row));
+                // The closest columns are the first to be merged, so drop them from the end right away
+                rows.par_iter_mut().flatten().for_each(|row| {
+                    while row
+                        .last()
+                        .is_some_and(|entry| alive[entry.index as usize].is_none())
+                    {
+                        row.pop();
+                    }
+                });
+                // Remove the rest of the merged columns once half of them are gone
+                if 2 * n_leaves <= *compacted_at {
+                    rows.par_iter_mut()
+                        .flatten()
+                        .for_each(|row| row.retain(|entry| alive[entry.index as usize].is_some()));
+                    *compacted_at = n_leaves;
+                }
+            }
+        }
+    }
+}
+
+pub(crate) enum RowIter<'a, T> {
+    BTree(btree_set::Iter<'a, Node<T>>),
+    SortedArray(std::iter::Rev<std::slice::Iter<'a, Entry>>, &'a [Option<T>]),
+}
+
+impl<T: Float> Iterator for RowIter<'_, T> {
+    type Item = (usize, T);
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            RowIter::BTree(iter) => iter.next().map(|node| (node.index, node.value)),
+            RowIter::SortedArray(iter, alive) => iter
+                .find(|entry| alive[entry.index as usize].is_some())
+                .map(|entry| (entry.index as usize, T::from_f64(entry.value as f64))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entries_are_lower_bounds() {
+        for value in [0.1, 1.0 / 3.0, 2.0, 1e300, -0.7] {
+            let entry = Entry::new(0, value);
+            assert!(entry.value as f64 <= value);
+        }
+        assert_eq!(Entry::new(0, 0.5).value, 0.5);
+    }
+
+    #[test]
+    fn test_sorted_arrays_skip_merged_columns() {
+        let distances = vec![
+            Some(vec![5.0, 9.0, 9.0]),
+            Some(vec![10.0, 10.0]),
+            Some(vec![8.0]),
+            Some(vec![]),
+        ];
+        let mut rows = Rows::build(&distances, RowStorage::SortedArray);
+        let mut alive = vec![Some(0.0); 4];
+        let columns: Vec<(usize, f64)> = rows.row(3, &alive).unwrap().collect();
+        assert_eq!(columns, vec![(2, 8.0), (0, 9.0), (1, 10.0)]);
+        // Merge 0 and 1 into 4
+        alive[0] = None;
+        alive[1] = None;
+        alive.push(Some(0.0));
+        let old = |_| (0.0, 0.0);
+        rows.merge(0, 1, old, &[(2, 7.0), (3, 7.0)], 3, &alive);
+        let columns: Vec<(usize, f64)> = rows.row(3, &alive).unwrap().collect();
+        assert_eq!(columns, vec![(2, 8.0)]);
+        let columns: Vec<(usize, f64)> = rows.row(4, &alive).unwrap().collect();
+        assert_eq!(columns, vec![(2, 7.0), (3, 7.0)]);
+        assert!(rows.row(0, &alive).is_none());
+    }
+}
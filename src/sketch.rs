@@ -0,0 +1,294 @@
+use std::io;
+
+use rayon::prelude::*;
+
+use crate::{DistanceMatrix, Error, Result};
+
+/// How the k-mers of a genome are summarised
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SketchMethod {
+    /// Bottom-s MinHash sketch (as [Mash](https://github.com/marbl/Mash)): the s smallest k-mer hashes
+    MinHash {
+        /// Number of hashes kept per genome
+        sketch_size: usize,
+    },
+    /// Every distinct k-mer of the genome
+    Exact,
+}
+
+/// Sorted and distinct hashes of the canonical k-mers of a genome
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sketch {
+    /// Name of the genome
+    pub name: String,
+    hashes: Vec<u64>,
+}
+
+impl Sketch {
+    /// Number of hashes of the sketch
+    pub fn len(&self) -> usize {
+        self.hashes.len()
+    }
+    /// Whether the genome had no valid k-mer
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+}
+
+/// Sketches unaligned genomes and estimates their [Mash distances](https://doi.org/10.1186/s13059-016-0997-x)
+/// from the Jaccard index of their canonical k-mers.
+#[derive(Debug, Clone)]
+pub struct Sketcher {
+    k: usize,
+    method: SketchMethod,
+}
+
+impl Default for Sketcher {
+    /// Default sketcher (k = 21 and MinHash sketches of 1000 hashes, as Mash)
+    fn default() -> Self {
+        Sketcher {
+            k: 21,
+            method: SketchMethod::MinHash { sketch_size: 1000 },
+        }
+    }
+}
+
+impl Sketcher {
+    /// Construct sketcher from parameters: a k-mer size between 1 and 32, and a method that keeps
+    /// at least one hash
+    pub fn build(k: usize, method: SketchMethod) -> Result<Self> {
+        if !(1..=32).contains(&k) {
+            return Err(Error::InvalidParameter(
+                "k-mer size must be between 1 and 32".to_string(),
+            ));
+        }
+        if method == (SketchMethod::MinHash { sketch_size: 0 }) {
+            return Err(Error::InvalidParameter(
+                "sketch size cannot be 0".to_string(),
+            ));
+        }
+        Ok(Sketcher { k, method })
+    }
+    /// Set the k-mer size (between 1 and 32)
+    pub fn set_kmer_size(self, k: usize) -> Result<Self> {
+        Self::build(k, self.method)
+    }
+    /// Set the sketching method
+    pub fn set_method(self, method: SketchMethod) -> Result<Self> {
+        Self::build(self.k, method)
+    }
+    /// Sketch the k-mers of the records of a genome (contigs, chromosomes or plasmids).
+    /// K-mers with characters other than A, C, G and T are skipped.
+    pub fn sketch<S: AsRef<[u8]>>(&self, name: &str, records: &[S]) -> Sketch {
+        let mut hashes: Vec<u64> = records
+            .iter()
+            .flat_map(|record| canonical_kmers(record.as_ref(), self.k))
+            .map(hash)
+            .collect();
+        hashes.sort_unstable();
+        hashes.dedup();
+        if let SketchMethod::MinHash { sketch_size } = self.method {
+            hashes.truncate(sketch_size);
+        }
+        Sketch {
+            name: name.to_string(),
+            hashes,
+        }
+    }
+    /// Read a genome from a FASTA file, with any number of records, and sketch it
+    pub fn sketch_fasta<R>(&self, name: &str, reader: R) -> Result<Sketch>
+    where
+        R: io::BufRead,
+    {
+        let mut records: Vec<Vec<u8>> = Vec::new();
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.starts_with('>') {
+                records.push(Vec::new());
+            } else if !line.trim().is_empty() {
+                let record = records.last_mut().ok_or_else(|| {
+                    Error::parse(index + 1, "sequence found before the first header")
+                })?;
+                record.extend(
+                    line.bytes()
+                        .filter(|c| !c.is_ascii_whitespace())
+                        .map(|c| c.to_ascii_uppercase()),
+                );
+            }
+        }
+        Ok(self.sketch(name, &records))
+    }
+    /// Estimated Jaccard index between the k-mers of two genomes
+    pub fn jaccard(&self, a: &Sketch, b: &Sketch) -> f64 {
+        let limit = match self.method {
+            SketchMethod::MinHash { sketch_size } => sketch_size,
+            SketchMethod::Exact => usize::MAX,
+        };
+        // Walk the union in order, as far as the sketch size, counting the shared hashes
+        let (mut i, mut j, mut union, mut shared) = (0, 0, 0, 0);
+        while union < limit && (i < a.hashes.len() || j < b.hashes.len()) {
+            match (a.hashes.get(i), b.hashes.get(j)) {
+                (Some(x), Some(y)) if x == y => {
+                    shared += 1;
+                    i += 1;
+                    j += 1;
+                }
+                (Some(x), Some(y)) if x < y => i += 1,
+                (Some(_), None) => i += 1,
+                _ => j += 1,
+            }
+            union += 1;
+        }
+        if union == 0 {
+            0.0
+        } else {
+            shared as f64 / union as f64
+        }
+    }
+    /// Mash distance, -ln(2j / (1 + j)) / k. Genomes without shared k-mers get a distance of 1.
+    pub fn distance(&self, a: &Sketch, b: &Sketch) -> f64 {
+        let j = self.jaccard(a, b);
+        if j == 0.0 {
+            return 1.0;
+        }
+        (-(2.0 * j / (1.0 + j)).ln() / self.k as f64).min(1.0)
+    }
+    /// Distance between every pair of genomes, computing the rows in parallel
+    pub fn distance_matrix(&self, sketches: &[Sketch]) -> Result<DistanceMatrix> {
+        let n = sketches.len();
+        let lower: Vec<f64> = (0..n)
+            .into_par_iter()
+            .flat_map_iter(|i| (0..i).map(move |j| self.distance(&sketches[i], &sketches[j])))
+            .collect();
+        let names = sketches.iter().map(|sketch| sketch.name.clone()).collect();
+        DistanceMatrix::from_lower_triangle(lower, names)
+    }
+}
+
+/// 2-bit encoding of the k-mers of a sequence, as the minimum of the k-mer and its reverse complement
+fn canonical_kmers(seq: &[u8], k: usize) -> impl Iterator<Item = u64> + '_ {
+    let mask = if k == 32 {
+        u64::MAX
+    } else {
+        (1 << (2 * k)) - 1
+    };
+    let shift = 2 * (k as u64 - 1);
+    let (mut forward, mut reverse, mut valid) = (0u64, 0u64, 0);
+    seq.iter().filter_map(move |c| {
+        let x = match c {
+            b'A' => 0,
+            b'C' => 1,
+            b'G' => 2,
+            b'T' => 3,
+            _ => {
+                valid = 0;
+                return None;
+            }
+        };
+        forward = ((forward << 2) | x) & mask;
+        reverse = (reverse >> 2) | ((3 - x) << shift);
+        valid += 1;
+        (valid >= k).then(|| forward.min(reverse))
+    })
+}
+
+/// Finalizer of MurmurHash3, so that the smallest hashes are a uniform sample of the k-mers
+fn hash(mut x: u64) -> u64 {
+    x ^= x >> 33;
+    x = x.wrapping_mul(0xff51afd7ed558ccd);
+    x ^= x >> 33;
+    x = x.wrapping_mul(0xc4ceb9fe1a85ec53);
+    x ^ (x >> 33)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canonical_kmers() {
+        let kmers: Vec<u64> = canonical_kmers(b"ACGTNAAC", 2).collect();
+        // AC, CG, GT (canonical AC), AA, AC
+        assert_eq!(kmers, vec![0b0001, 0b0110, 0b0001, 0b0000, 0b0001]);
+        // A sequence and its reverse complement have the same k-mers
+        let sketcher = Sketcher::build(5, SketchMethod::Exact).unwrap();
+        let a = sketcher.sketch("a", &["ACGGTCATTGACCA"]);
+        let b = sketcher.sketch("b", &["TGGTCAATGACCGT"]);
+        assert_eq!(a.hashes, b.hashes);
+    }
+
+    #[test]
+    fn test_exact_jaccard() {
+        let sketcher = Sketcher::build(3, SketchMethod::Exact).unwrap();
+        let a = sketcher.sketch("a", &["AAACCC"]);
+        let b = sketcher.sketch("b", &["AAACCG"]);
+        // AAA, AAC, ACC, CCC (GGG) against AAA, AAC, ACC, CCG
+        assert_eq!(sketcher.jaccard(&a, &b), 3.0 / 5.0);
+        assert_eq!(sketcher.distance(&a, &a), 0.0);
+        let c = sketcher.sketch("c", &["CGCGCG"]);
+        assert_eq!(sketcher.distance(&a, &c), 1.0);
+        assert_eq!(sketcher.distance(&a, &c), sketcher.distance(&c, &a));
+    }
+
+    #[test]
+    fn test_minhash_estimates_distance() {
+        use rand::{Rng, SeedableRng};
+        let mut rng = rand::rngs::StdRng::seed_from_u64(11);
+        let genome: Vec<u8> = (0..20_000).map(|_| b"ACGT"[rng.gen_range(0..4)]).collect();
+        // 1% of the sites mutated
+        let mut mutated = genome.clone();
+        for site in (0..mutated.len()).step_by(100) {
+            mutated[site] = if mutated[site] == b'A' { b'C' } else { b'A' };
+        }
+        let exact = Sketcher::build(21, SketchMethod::Exact).unwrap();
+        let minhash = Sketcher::default();
+        let d = exact.distance(
+            &exact.sketch("a", &[&genome]),
+            &exact.sketch("b", &[&mutated]),
+        );
+        let estimate = minhash.distance(
+            &minhash.sketch("a", &[&genome]),
+            &minhash.sketch("b", &[&mutated]),
+        );
+        assert!((d - 0.01).abs() < 0.002);
+        assert!((estimate - d).abs() < 0.003);
+    }
+
+    #[test]
+    fn test_distance_matrix() {
+        let sketcher = Sketcher::build(4, SketchMethod::Exact).unwrap();
+        let input = ">contig1
+ACGTTGCA
+>contig2
+GGGCCCAT
+"
+        .as_bytes();
+        let a = sketcher.sketch_fasta("a", input).unwrap();
+        let b = sketcher.sketch("b", &["ACGTTGCA", "GGGCCCAA"]);
+        let c = sketcher.sketch("c", &["TTTTTTTT"]);
+        let d = sketcher.distance_matrix(&[a, b, c]).unwrap();
+        assert_eq!(d.names, vec!["a", "b", "c"]);
+        assert!(d.get(0, 1) > 0.0 && d.get(0, 1) < 1.0);
+        assert_eq!(d.get(0, 2), 1.0);
+        assert!(d.validate().is_valid());
+    }
+
+    #[test]
+    fn test_invalid_parameters() {
+        let empty = SketchMethod::MinHash { sketch_size: 0 };
+        for k in [0, 33] {
+            assert!(matches!(
+                Sketcher::build(k, SketchMethod::Exact),
+                Err(Error::InvalidParameter(_))
+            ));
+            assert!(matches!(
+                Sketcher::default().set_kmer_size(k),
+                Err(Error::InvalidParameter(_))
+            ));
+        }
+        assert!(Sketcher::build(21, empty).is_err());
+        assert!(Sketcher::default().set_method(empty).is_err());
+        assert!(Sketcher::build(1, SketchMethod::Exact).is_ok());
+        assert!(Sketcher::build(32, SketchMethod::MinHash { sketch_size: 1 }).is_ok());
+    }
+}
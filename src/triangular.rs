@@ -0,0 +1,144 @@
+/// Symmetric matrix with a zero diagonal, packed as its lower triangle.
+///
+/// Row i holds the i distances d(i, 0), ..., d(i, i - 1), and the rows are stored one after the
+/// other in a single vector, so n taxa take n (n - 1) / 2 values.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct LowerTriangle<T> {
+    values: Vec<T>,
+    n: usize,
+}
+
+impl<T: Copy + Default> LowerTriangle<T> {
+    /// Number of values of the lower triangle of n taxa
+    pub fn len(n: usize) -> usize {
+        n * n.saturating_sub(1) / 2
+    }
+    /// Lower triangle of n taxa with every distance set to zero
+    pub fn zeros(n: usize) -> Self {
+        LowerTriangle {
+            values: vec![T::default(); Self::len(n)],
+            n,
+        }
+    }
+    /// Lower triangle from its packed values. None if there are not n (n - 1) / 2 of them.
+    pub fn from_values(values: Vec<T>, n: usize) -> Option<Self> {
+        (values.len() == Self::len(n)).then_some(LowerTriangle { values, n })
+    }
+    /// Number of taxa
+    pub fn size(&self) -> usize {
+        self.n
+    }
+    fn index(i: usize, j: usize) -> usize {
+        debug_assert!(j < i);
+        i * (i - 1) / 2 + j
+    }
+    /// Distance between i and j (zero on the diagonal)
+    pub fn get(&self, i: usize, j: usize) -> T {
+        match i.cmp(&j) {
+            std::cmp::Ordering::Greater => self.values[Self::index(i, j)],
+            std::cmp::Ordering::Less => self.values[Self::index(j, i)],
+            std::cmp::Ordering::Equal => T::default(),
+        }
+    }
+    /// Set the distance between i and j, which must be different
+    pub fn set(&mut self, i: usize, j: usize, value: T) {
+        let index = Self::index(i.max(j), i.min(j));
+        self.values[index] = value;
+    }
+    /// Distances d(i, 0), ..., d(i, i - 1)
+    pub fn row(&self, i: usize) -> &[T] {
+        let start = Self::len(i);
+        &self.values[start..start + i]
+    }
+    /// Mutable distances d(i, 0), ..., d(i, i - 1)
+    pub fn row_mut(&mut self, i: usize) -> &mut [T] {
+        let start = Self::len(i);
+        &mut self.values[start..start + i]
+    }
+    /// Mutable row n - 2, and the first n - 2 distances of row n - 1
+    pub fn last_two_rows_mut(&mut self) -> (&mut [T], &[T]) {
+        let n = self.n;
+        let (head, last) = self.values.split_at_mut(Self::len(n - 1));
+        (&mut head[Self::len(n - 2)..], &last[..n - 2])
+    }
+    /// Exchange the positions of taxa a and b
+    pub fn swap(&mut self, a: usize, b: usize) {
+        let (a, b) = (a.min(b), a.max(b));
+        if a == b {
+            return;
+        }
+        // d(a, k) and d(b, k) for k < a are the starts of both rows, swapped as whole slices
+        let (head, tail) = self.values.split_at_mut(Self::len(b));
+        head[Self::len(a)..Self::len(a) + a].swap_with_slice(&mut tail[..a]);
+        // d(k, a) is in the column of a for a < k < b, and both are in row k after b
+        for k in a + 1..self.n {
+            if k != b {
+                let (x, y) = (Self::index(k, a), Self::index(b.max(k), b.min(k)));
+                self.values.swap(x, y);
+            }
+        }
+    }
+    /// Remove the last taxon
+    pub fn pop(&mut self) {
+        self.n -= 1;
+        self.values.truncate(Self::len(self.n));
+    }
+    /// Convert every value. The vector is reused when both types have the same size.
+    pub fn map<U>(self, f: impl Fn(T) -> U) -> LowerTriangle<U> {
+        LowerTriangle {
+            values: self.values.into_iter().map(f).collect(),
+            n: self.n,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example() -> LowerTriangle<f64> {
+        // d(i, j) = 10 i + j for i > j
+        let mut t = LowerTriangle::zeros(4);
+        for i in 0..4 {
+            for j in 0..i {
+                t.set(i, j, (10 * i + j) as f64);
+            }
+        }
+        t
+    }
+
+    #[test]
+    fn test_accessors() {
+        let t = example();
+        assert_eq!(t.row(3), &[30.0, 31.0, 32.0]);
+        assert_eq!(t.get(1, 3), 31.0);
+        assert_eq!(t.get(2, 2), 0.0);
+        assert_eq!(t.row(2), &[20.0, 21.0]);
+        assert_eq!(t.row(0), &[] as &[f64]);
+        assert!(LowerTriangle::from_values(vec![1.0, 2.0], 3).is_none());
+    }
+
+    #[test]
+    fn test_swap_and_pop() {
+        let mut t = example();
+        t.swap(1, 3);
+        assert_eq!(t.get(1, 3), 31.0);
+        assert_eq!(t.get(0, 1), 30.0);
+        assert_eq!(t.get(0, 3), 10.0);
+        assert_eq!(t.get(1, 2), 32.0);
+        assert_eq!(t.get(2, 3), 21.0);
+        // Swapping back, in either order, restores the matrix
+        t.swap(3, 1);
+        assert_eq!(t, example());
+        t.swap(1, 3);
+        let (row, last) = t.last_two_rows_mut();
+        assert_eq!(row, &[20.0, 32.0]);
+        assert_eq!(last, &[10.0, 31.0]);
+        t.pop();
+        assert_eq!(t.size(), 3);
+        assert_eq!(
+            t,
+            LowerTriangle::from_values(vec![30.0, 20.0, 32.0], 3).unwrap()
+        );
+    }
+}
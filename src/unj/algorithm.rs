@@ -0,0 +1,27 @@
+use crate::{
+    distances::DistanceMatrix,
+    naive_nj::{merge_layout, terminate_nj, PhyloTree, QMatrix},
+    Result, Tree,
+};
+
+/// Unweighted neighbor joining, UNJ (Gascuel 1997). Neighbors and branch lengths are chosen as in the
+/// canonical algorithm, but the distances to the new node average those of i and j weighted by the
+/// number of taxa in each subtree, so every taxon has the same weight regardless of the sampling.
+pub fn unweighted_neighbor_joining(dist: DistanceMatrix) -> Result<Tree> {
+    let mut t = PhyloTree::build(&dist.names);
+    // Number of taxa of every cluster, following the layout of the QMatrix
+    let mut sizes = vec![1; dist.size()];
+    let mut q = QMatrix::<f64>::build(dist);
+    while q.n_leaves() > 3 {
+        let (i, j) = q.find_neighbors();
+        let (dist_ui, dist_uj) = q.new_node_distances(i, j);
+        let merged = sizes[i] + sizes[j];
+        let lambda = sizes[i] as f64 / merged as f64;
+        t.merge_neighbors(i, j, dist_ui, dist_uj);
+        q.update_with(i, j, |dik, djk| {
+            lambda * (dik - dist_ui) + (1.0 - lambda) * (djk - dist_uj)
+        });
+        merge_layout(&mut sizes, i, j, merged);
+    }
+    Ok(terminate_nj(t, q))
+}
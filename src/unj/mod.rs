@@ -0,0 +1,54 @@
+mod algorithm;
+pub use algorithm::unweighted_neighbor_joining;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distances::DistanceMatrix;
+    #[test]
+    fn test_example_wikipedia() {
+        let d = DistanceMatrix::build(
+            vec![
+                vec![0.0, 5.0, 9.0, 9.0, 8.0],
+                vec![5.0, 0.0, 10.0, 10.0, 9.0],
+                vec![9.0, 10.0, 0.0, 8.0, 7.0],
+                vec![9.0, 10.0, 8.0, 0.0, 3.0],
+                vec![8.0, 9.0, 7.0, 3.0, 0.0],
+            ],
+            vec![
+                "A".to_string(),
+                "B".to_string(),
+                "C".to_string(),
+                "D".to_string(),
+                "E".to_string(),
+            ],
+        )
+        .unwrap();
+        // The matrix is additive, so UNJ recovers the same tree as NJ
+        let tree = unweighted_neighbor_joining(d.clone()).unwrap();
+        let nj = crate::naive_nj::canonical_neighbor_joining::<f64>(d).unwrap();
+        assert_eq!(crate::robinson_foulds(&tree, &nj).unwrap(), 0);
+        assert!(crate::branch_score(&tree, &nj).unwrap() < 1e-12);
+    }
+
+    #[test]
+    fn test_uneven_clusters() {
+        // Not additive, and the second merge joins clusters of different sizes
+        let d = DistanceMatrix::build(
+            vec![
+                vec![0.0, 2.0, 4.0, 9.0, 10.0, 11.0],
+                vec![2.0, 0.0, 4.0, 9.0, 11.0, 10.0],
+                vec![4.0, 4.0, 0.0, 8.0, 9.0, 12.0],
+                vec![9.0, 9.0, 8.0, 0.0, 6.0, 7.0],
+                vec![10.0, 11.0, 9.0, 6.0, 0.0, 5.0],
+                vec![11.0, 10.0, 12.0, 7.0, 5.0, 0.0],
+            ],
+            (0..6).map(|i| format!("t{i}")).collect(),
+        )
+        .unwrap();
+        let tree = unweighted_neighbor_joining(d.clone()).unwrap();
+        let nj = crate::naive_nj::canonical_neighbor_joining::<f64>(d).unwrap();
+        assert_eq!(tree.node_count(), nj.node_count());
+        assert!(crate::branch_score(&tree, &nj).unwrap() > 0.0);
+    }
+}
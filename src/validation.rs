@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::{DistanceMatrix, Error, Result};
+
+/// What [`DistanceMatrix::validate_with`] does when problems remain after the repairs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ValidationAction {
+    /// Return an [`Error::Validation`]
+    #[default]
+    Error,
+    /// Return the report and let the caller decide (e.g. print a warning)
+    Warn,
+}
+
+/// Policy used to validate (and optionally repair) a distance matrix
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ValidationPolicy {
+    /// What to do with the problems that are not repaired
+    pub action: ValidationAction,
+    /// Replace asymmetric pairs by their average. Otherwise the solvers use the lower triangle.
+    pub symmetrize: bool,
+    /// Replace negative distances by zero
+    pub clamp_negatives: bool,
+}
+
+/// Problems found in a distance matrix. Every field holds the offending indices.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    /// Pairs (i, j), with i < j, where d(i, j) != d(j, i) in the square matrix that was read
+    pub asymmetric: Vec<(usize, usize)>,
+    /// Taxa whose distance to themselves was not zero in the square matrix that was read
+    pub nonzero_diagonal: Vec<usize>,
+    /// Pairs (i, j), with i < j, with a negative distance
+    pub negative: Vec<(usize, usize)>,
+    /// Pairs (i, j), with i < j, whose distance is NaN or infinite
+    pub non_finite: Vec<(usize, usize)>,
+    /// Pairs of taxa (i, j), with i < j, sharing the same name
+    pub duplicate_names: Vec<(usize, usize)>,
+}
+
+impl ValidationReport {
+    /// Whether no problem was found
+    pub fn is_valid(&self) -> bool {
+        self.asymmetric.is_empty()
+            && self.nonzero_diagonal.is_empty()
+            && self.negative.is_empty()
+            && self.non_finite.is_empty()
+            && self.duplicate_names.is_empty()
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn write_items<T: fmt::Debug>(
+            f: &mut fmt::Formatter<'_>,
+            first: &mut bool,
+            what: &str,
+            items: &[T],
+        ) -> fmt::Result {
+            if items.is_empty() {
+                return Ok(());
+            }
+            if !*first {
+                write!(f, "; ")?;
+            }
+            *first = false;
+            write!(f, "{} {what}", items.len())?;
+            let shown: Vec<String> = items.iter().take(5).map(|x| format!("{x:?}")).collect();
+            write!(f, " {}", shown.join(", "))?;
+            if items.len() > 5 {
+                write!(f, ", ...")?;
+            }
+            Ok(())
+        }
+        if self.is_valid() {
+            return write!(f, "no problems found");
+        }
+        let mut first = true;
+        write_items(f, &mut first, "asymmetric pairs", &self.asymmetric)?;
+        write_items(
+            f,
+            &mut first,
+            "non-zero diagonal entries",
+            &self.nonzero_diagonal,
+        )?;
+        write_items(f, &mut first, "negative distances", &self.negative)?;
+        write_items(f, &mut first, "NaN or infinite distances", &self.non_finite)?;
+        write_items(f, &mut first, "duplicated names", &self.duplicate_names)
+    }
+}
+
+impl DistanceMatrix {
+    /// Check that the matrix has no negative, NaN or infinite distances and that taxon names are unique.
+    /// The asymmetric pairs and the non-zero diagonal of a square matrix are kept aside when it is
+    /// packed into its lower triangle, so they are reported too.
+    pub fn validate(&self) -> ValidationReport {
+        let mut report = ValidationReport {
+            asymmetric: self
+                .unpacked
+                .upper
+                .iter()
+                .map(|&(i, j, _)| (i, j))
+                .collect(),
+            nonzero_diagonal: self.unpacked.diagonal.iter().map(|&(i, _)| i).collect(),
+            ..Default::default()
+        };
+        let mut seen = HashMap::with_capacity(self.names.len());
+        for (j, name) in self.names.iter().enumerate() {
+            if let Some(i) = seen.insert(name.as_str(), j) {
+                report.duplicate_names.push((i, j));
+            }
+        }
+        // The lower triangle is read in order, so the pairs are sorted afterwards
+        for i in 1..self.size() {
+            for j in 0..i {
+                let value = self.get(i, j);
+                if !value.is_finite() {
+                    report.non_finite.push((j, i));
+                } else if value < 0.0 {
+                    report.negative.push((j, i));
+                }
+            }
+        }
+        report.non_finite.sort_unstable();
+        report.negative.sort_unstable();
+        report
+    }
+
+    /// Validate the matrix, repair it according to `policy` and fail if problems remain
+    /// (unless the policy only warns). The returned report lists every problem found,
+    /// including the repaired ones.
+    pub fn validate_with(&mut self, policy: ValidationPolicy) -> Result<ValidationReport> {
+        let mut averaged = Vec::new();
+        if policy.symmetrize {
+            for (i, j, upper) in std::mem::take(&mut self.unpacked.upper) {
+                self.set(i, j, (upper + self.get(i, j)) / 2.0)?;
+                averaged.push((i, j));
+            }
+        }
+        // Negative distances are found after averaging, as the solvers will read them
+        let mut remaining = self.validate();
+        let mut report = remaining.clone();
+        report.asymmetric.extend(averaged);
+        if policy.clamp_negatives {
+            for &(i, j) in &report.negative {
+                self.set(i, j, 0.0)?;
+            }
+            remaining.negative.clear();
+        }
+        if policy.action == ValidationAction::Error && !remaining.is_valid() {
+            return Err(Error::Validation(remaining.to_string()));
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("T{i}")).collect()
+    }
+
+    #[test]
+    fn test_valid_matrix() {
+        let d = DistanceMatrix::build(
+            vec![
+                vec![0.0, 5.0, 9.0],
+                vec![5.0, 0.0, 10.0],
+                vec![9.0, 10.0, 0.0],
+            ],
+            names(3),
+        )
+        .unwrap();
+        assert!(d.validate().is_valid());
+    }
+
+    #[test]
+    fn test_report_offending_indices() {
+        let mut names = names(3);
+        names[2] = "T0".to_string();
+        let d = DistanceMatrix::build(
+            vec![
+                vec![1.0, 5.0, f64::NAN],
+                vec![4.0, 0.0, -1.0],
+                vec![9.0, -1.0, 0.0],
+            ],
+            names,
+        )
+        .unwrap();
+        let report = d.validate();
+        assert_eq!(report.asymmetric, vec![(0, 1)]);
+        assert_eq!(report.nonzero_diagonal, vec![0]);
+        assert_eq!(report.negative, vec![(1, 2)]);
+        assert_eq!(report.non_finite, vec![(0, 2)]);
+        assert_eq!(report.duplicate_names, vec![(0, 2)]);
+    }
+
+    #[test]
+    fn test_ragged_rows() {
+        let d = DistanceMatrix::build(vec![vec![0.0, 1.0], vec![1.0]], names(2));
+        assert!(matches!(d, Err(Error::Validation(_))));
+    }
+
+    #[test]
+    fn test_policies() {
+        let matrix = vec![
+            vec![0.0, 4.0, 9.0],
+            vec![6.0, 0.0, -1.0],
+            vec![9.0, -1.0, 0.0],
+        ];
+        let mut d = DistanceMatrix::build(matrix, names(3)).unwrap();
+        assert!(matches!(
+            d.validate_with(ValidationPolicy::default()),
+            Err(Error::Validation(_))
+        ));
+        // Warn returns the report and leaves the matrix untouched
+        let policy = ValidationPolicy {
+            action: ValidationAction::Warn,
+            ..Default::default()
+        };
+        let report = d.validate_with(policy).unwrap();
+        assert_eq!(report.asymmetric, vec![(0, 1)]);
+        assert_eq!(d.get(0, 1), 6.0);
+        assert_eq!(d.get(1, 2), -1.0);
+        assert_eq!(d.validate(), report);
+        // Repairs
+        let policy = ValidationPolicy {
+            symmetrize: true,
+            clamp_negatives: true,
+            ..Default::default()
+        };
+        d.validate_with(policy).unwrap();
+        assert_eq!(
+            d.to_rows(),
+            vec![
+                vec![0.0, 5.0, 9.0],
+                vec![5.0, 0.0, 0.0],
+                vec![9.0, 0.0, 0.0],
+            ]
+        );
+        assert!(d.validate().is_valid());
+    }
+
+    #[test]
+    fn test_warn_and_symmetrize_differ() {
+        let matrix = vec![
+            vec![0.0, 2.0, 9.0, 9.0],
+            vec![8.0, 0.0, 9.0, 9.0],
+            vec![9.0, 9.0, 0.0, 4.0],
+            vec![9.0, 9.0, 4.0, 0.5],
+        ];
+        let warn = ValidationPolicy {
+            action: ValidationAction::Warn,
+            ..Default::default()
+        };
+        let symmetrize = ValidationPolicy {
+            symmetrize: true,
+            ..warn
+        };
+        let mut warned = DistanceMatrix::build(matrix.clone(), names(4)).unwrap();
+        let mut averaged = DistanceMatrix::build(matrix, names(4)).unwrap();
+        let report = warned.validate_with(warn).unwrap();
+        assert_eq!(averaged.validate_with(symmetrize).unwrap(), report);
+        assert_eq!(report.asymmetric, vec![(0, 1)]);
+        assert_eq!(report.nonzero_diagonal, vec![3]);
+        // Warn leaves the lower triangle, and symmetrize averages it
+        assert_eq!(warned.get(0, 1), 8.0);
+        assert_eq!(averaged.get(0, 1), 5.0);
+        assert_eq!(warned.validate().asymmetric, vec![(0, 1)]);
+        assert!(averaged.validate().asymmetric.is_empty());
+        // The non-zero diagonal is not repaired, so it is still reported
+        assert_eq!(averaged.validate().nonzero_diagonal, vec![3]);
+        let tree = |d: DistanceMatrix| {
+            crate::NeighborJoiningSolver::<crate::Canonical>::default(d)
+                .solve()
+                .unwrap()
+        };
+        let (warned, averaged) = (tree(warned), tree(averaged));
+        let branch = |t: &crate::Tree| {
+            let leaf = t.node_indices().find(|n| t[*n] == "T0").unwrap();
+            *t.edges(leaf).next().unwrap().weight()
+        };
+        assert_ne!(branch(&warned), branch(&averaged));
+    }
+}
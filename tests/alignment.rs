@@ -0,0 +1,144 @@
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+
+fn run(args: &[&str], input: &str) -> String {
+    let mut child = Command::new("target/debug/speedytree")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Failed to spawn child process");
+
+    // Close stdin so the alignment readers see the end of the input
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(input.as_bytes()).unwrap();
+    drop(stdin);
+
+    let mut output = String::new();
+    child
+        .stdout
+        .as_mut()
+        .unwrap()
+        .read_to_string(&mut output)
+        .unwrap();
+
+    let status = child.wait().unwrap();
+    assert!(status.success());
+    output
+}
+
+#[test]
+fn fasta_alignment() {
+    let input = ">a
+ACGTACGTAC
+>b
+ACGTACGTTT
+>c
+ACGAACCTTT
+>d
+TCGAACCTTA
+";
+    let expected_output =
+        "((c:0.025000000000000023,d:0.175):0.17500000000000003,a:0.175,b:0.025000000000000023);";
+    let output = run(&["--naive", "--input-format", "fasta"], input);
+    assert_eq!(output.trim(), expected_output);
+}
+
+#[test]
+fn phylip_alignment() {
+    let input = "4 10
+a ACGTACGTAC
+b ACGTACGTTT
+c ACGAACCTTT
+d TCGAACCTTA
+";
+    let expected_output =
+        "((c:0.025000000000000023,d:0.175):0.17500000000000003,a:0.175,b:0.025000000000000023);";
+    let output = run(&["--naive", "--input-format", "phylip-alignment"], input);
+    assert_eq!(output.trim(), expected_output);
+}
+
+#[test]
+fn stockholm_alignment() {
+    let input = std::fs::read_to_string("data/58_AAR2.sth").unwrap();
+    let output = run(&["--input-format", "sth"], &input);
+    assert!(output.trim().ends_with(';'));
+    assert_eq!(output.matches("_").count(), 58);
+}
+
+#[test]
+fn corrected_distances() {
+    let input = ">a
+ACGTACGTAC
+>b
+ACGTACGTTT
+>c
+ACGAACCTTT
+>d
+TCGAACCTTA
+";
+    let p_distance = run(&["--naive", "--input-format", "fasta"], input);
+    for model in ["jc", "k2p", "tn", "f84", "logdet"] {
+        let output = run(
+            &["--naive", "--input-format", "fasta", "--model", model],
+            input,
+        );
+        assert!(output.trim().ends_with(';'));
+        assert_ne!(output, p_distance);
+    }
+}
+
+#[test]
+fn nucleotide_model_on_proteins() {
+    let status = Command::new("target/debug/speedytree")
+        .args(["--input-format", "sth", "--model", "k2p"])
+        .stdin(std::fs::File::open("data/58_AAR2.sth").unwrap())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(!status.success());
+}
+
+#[test]
+fn protein_models() {
+    let input = std::fs::read_to_string("data/58_AAR2.sth").unwrap();
+    for model in ["poisson", "kimura", "lg"] {
+        let output = run(&["--input-format", "sth", "--model", model], &input);
+        assert!(output.trim().ends_with(';'));
+        assert_eq!(output.matches("_").count(), 58);
+    }
+}
+
+#[test]
+fn rate_variation() {
+    let input = ">a
+ACGTACGTAC
+>b
+ACGTACGTTT
+>c
+ACGAACCTTT
+>d
+TCGAACCTTA
+";
+    let uniform = run(
+        &["--naive", "--input-format", "fasta", "--model", "k2p"],
+        input,
+    );
+    let gamma = run(
+        &[
+            "--naive",
+            "--input-format",
+            "fasta",
+            "--model",
+            "k2p",
+            "--gamma",
+            "0.5",
+            "--pinv",
+            "0.1",
+        ],
+        input,
+    );
+    assert!(gamma.trim().ends_with(';'));
+    assert_ne!(gamma, uniform);
+}
@@ -0,0 +1,58 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn run(args: &[&str], input: &str) -> Output {
+    let mut child = Command::new("target/debug/speedytree")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to spawn child process");
+    // A rejected invocation may exit before reading its input
+    let _ = child.stdin.take().unwrap().write_all(input.as_bytes());
+    child.wait_with_output().unwrap()
+}
+
+//https://en.wikipedia.org/wiki/Neighbor_joining
+const INPUT: &str = "5
+a	0	5	9	9	8
+b	5	0	10	10	9
+c	9	10	0	8	7
+d	9	10	8	0	3
+e	8	9	7	3	0
+";
+
+#[test]
+fn auto_reports_its_strategy() {
+    let output = run(&["--auto"], INPUT);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap().trim(),
+        "(((d:2.0,e:1.0):2.0,c:4.0):3.0,a:2.0,b:3.0);"
+    );
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap().trim(),
+        "Auto strategy: canonical, double precision, about 0.0 MB"
+    );
+    let output = run(&["--auto", "--f32"], INPUT);
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("single precision"), "{stderr}");
+}
+
+#[test]
+fn auto_rejects_tuning_options() {
+    for args in [
+        &["--auto", "--chunk-size", "4"][..],
+        &["--auto", "--naive-percentage", "50"],
+        &["--auto", "--naive"],
+        &["--memory-budget", "100"],
+        // More megabytes than bytes can be addressed
+        &["--auto", "--memory-budget", "18446744073709551615"],
+        &["--auto", "--memory-budget", "18446744073710"],
+    ] {
+        let output = run(args, INPUT);
+        assert!(!output.status.success(), "{args:?}");
+    }
+}
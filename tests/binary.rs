@@ -0,0 +1,65 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+const PRIMATES: &str = "6
+    Mouse     0.0000 1.5232 1.4841 1.4465 1.4389 1.4629 
+    Gibbon    1.5232 0.0000 0.7115 0.5958 0.6179 0.5583 
+    Orang     1.4841 0.7115 0.0000 0.4631 0.5061 0.4710 
+    Gorilla   1.4465 0.5958 0.4631 0.0000 0.3484 0.3083 
+    Chimp     1.4389 0.6179 0.5061 0.3484 0.0000 0.2692 
+    Human     1.4629 0.5583 0.4710 0.3083 0.2692 0.0000
+";
+
+fn run_speedytree(args: &[&str], input: &str) -> Output {
+    let mut child = Command::new("target/debug/speedytree")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to spawn child process");
+    child
+        .stdin
+        .as_mut()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn convert_and_solve_binary_matrix() {
+    let dir = std::env::temp_dir().join("speedytree_binary_convert");
+    std::fs::create_dir_all(&dir).unwrap();
+    for (precision, name) in [
+        (&[][..], "primates.bin"),
+        (&["--f32"][..], "primates32.bin"),
+    ] {
+        let path = dir.join(name);
+        let path = path.to_str().unwrap();
+        let output = run_speedytree(&[&["--convert", path], precision].concat(), PRIMATES);
+        assert!(output.status.success());
+        assert!(output.stdout.is_empty());
+        for algorithm in ["--naive", "--rapidnj", "--hybrid"] {
+            let expected = run_speedytree(&[&[algorithm], precision].concat(), PRIMATES);
+            let args = [&[algorithm, "--binary", path], precision].concat();
+            let output = run_speedytree(&args, "");
+            assert!(output.status.success());
+            assert_eq!(output.stdout, expected.stdout);
+        }
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn text_is_not_a_binary_matrix() {
+    let dir = std::env::temp_dir().join("speedytree_binary_text");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("primates.phy");
+    std::fs::write(&path, PRIMATES).unwrap();
+    let output = run_speedytree(&["--binary", path.to_str().unwrap()], "");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("not a speedytree binary distance matrix"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
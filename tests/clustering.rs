@@ -0,0 +1,57 @@
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+
+fn run_clustering(linkage: &str, input: &str) -> String {
+    let mut child = Command::new("target/debug/speedytree")
+        .args(["--linkage", linkage])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Failed to spawn child process");
+
+    let stdin = child.stdin.as_mut().unwrap();
+    stdin.write_all(input.as_bytes()).unwrap();
+
+    let mut output = String::new();
+    child
+        .stdout
+        .as_mut()
+        .unwrap()
+        .read_to_string(&mut output)
+        .unwrap();
+
+    let status = child.wait().unwrap();
+    assert!(status.success());
+    output
+}
+
+const WIKIPEDIA: &str = "5
+a	0	17	21	31	23
+b	17	0	30	34	21
+c	21	30	0	28	39
+d	31	34	28	0	43
+e	23	21	39	43	0
+";
+
+#[test]
+fn wikipedia_example_upgma() {
+    //https://en.wikipedia.org/wiki/UPGMA
+    let expected_output = "((e:11.0,(a:8.5,b:8.5):2.5):5.5,(d:14.0,c:14.0):2.5);";
+    assert_eq!(run_clustering("upgma", WIKIPEDIA).trim(), expected_output);
+}
+
+#[test]
+fn wikipedia_example_wpgma() {
+    //https://en.wikipedia.org/wiki/WPGMA
+    let expected_output = "((e:11.0,(a:8.5,b:8.5):2.5):6.5,(d:14.0,c:14.0):3.5);";
+    assert_eq!(run_clustering("wpgma", WIKIPEDIA).trim(), expected_output);
+}
+
+#[test]
+fn complete_linkage() {
+    let expected_output = "((e:11.5,(a:8.5,b:8.5):3.0):10.0,(d:14.0,c:14.0):7.5);";
+    assert_eq!(
+        run_clustering("complete", WIKIPEDIA).trim(),
+        expected_output
+    );
+}
@@ -0,0 +1,71 @@
+use rand::{Rng, SeedableRng};
+use std::fs;
+use std::process::Command;
+
+/// Random genome and three descendants, with more mutations the further they are
+fn write_genomes(dir: &std::path::Path) -> Vec<String> {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(5);
+    let ancestor: Vec<u8> = (0..10_000).map(|_| b"ACGT"[rng.gen_range(0..4)]).collect();
+    let mut paths = Vec::new();
+    for (i, rate) in [0, 50, 100, 200].iter().enumerate() {
+        let mut genome = ancestor.clone();
+        for _ in 0..*rate {
+            let site = rng.gen_range(0..genome.len());
+            genome[site] = b"ACGT"[rng.gen_range(0..4)];
+        }
+        let (first, second) = genome.split_at(6_000);
+        let path = dir.join(format!("genome{i}.fasta"));
+        let content = format!(
+            ">contig1\n{}\n>contig2\n{}\n",
+            String::from_utf8_lossy(first),
+            String::from_utf8_lossy(second)
+        );
+        fs::write(&path, content).unwrap();
+        paths.push(path.to_string_lossy().to_string());
+    }
+    paths
+}
+
+#[test]
+fn minhash_genomes() {
+    let dir = std::env::temp_dir().join("speedytree_genomes");
+    fs::create_dir_all(&dir).unwrap();
+    let paths = write_genomes(&dir);
+    for sketch_size in ["1000", "0"] {
+        let output = Command::new("target/debug/speedytree")
+            .arg("--naive")
+            .arg("--genomes")
+            .args(&paths)
+            .args(["--kmer-size", "15", "--sketch-size", sketch_size])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let newick = String::from_utf8(output.stdout).unwrap();
+        assert!(newick.trim().ends_with(';'));
+        for i in 0..4 {
+            assert!(newick.contains(&format!("genome{i}:")));
+        }
+    }
+}
+
+#[test]
+fn invalid_kmer_sizes() {
+    let dir = std::env::temp_dir().join("speedytree_genomes_kmers");
+    fs::create_dir_all(&dir).unwrap();
+    let paths = write_genomes(&dir);
+    for kmer_size in ["0", "33"] {
+        let output = Command::new("target/debug/speedytree")
+            .arg("--genomes")
+            .args(&paths)
+            .args(["--kmer-size", kmer_size])
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(
+            stderr.contains("k-mer size must be between 1 and 32"),
+            "{stderr}"
+        );
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -14,7 +14,9 @@ fn wikipedia_example_hybrid() {
 
     let expected_output = "(((e:1.0,d:2.0):2.0,c:4.0):3.0,a:2.0,b:3.0);";
 
+    // The first iteration is done by rapidnj, the others by the naive algorithm
     let mut child = Command::new("target/debug/speedytree")
+        .args(["--naive-percentage", "50"])
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .spawn()
@@ -48,7 +50,9 @@ fn simple_tree_hybrid() {
     Human     1.4629 0.5583 0.4710 0.3083 0.2692 0.0000
 ";
     let expected_output = "((((Chimp:0.15009999999999988,Human:0.11910000000000012):0.03552500000000003,Gorilla:0.158225):0.03500000000000006,Orang:0.27664999999999997):0.05954999999999988,Mouse:1.1802124999999999,Gibbon:0.3429875000000002);";
+    // The first iteration is done by rapidnj, the others by the naive algorithm
     let mut child = Command::new("target/debug/speedytree")
+        .args(["--naive-percentage", "50"])
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .spawn()
@@ -70,3 +74,72 @@ fn simple_tree_hybrid() {
 
     assert_eq!(output.trim(), expected_output);
 }
+
+#[test]
+fn adaptive_switch_hybrid() {
+    let input = "6
+    Mouse     0.0000 1.5232 1.4841 1.4465 1.4389 1.4629 
+    Gibbon    1.5232 0.0000 0.7115 0.5958 0.6179 0.5583 
+    Orang     1.4841 0.7115 0.0000 0.4631 0.5061 0.4710 
+    Gorilla   1.4465 0.5958 0.4631 0.0000 0.3484 0.3083 
+    Chimp     1.4389 0.6179 0.5061 0.3484 0.0000 0.2692 
+    Human     1.4629 0.5583 0.4710 0.3083 0.2692 0.0000
+";
+    let expected_output = "((((Chimp:0.15009999999999988,Human:0.11910000000000012):0.03552500000000003,Gorilla:0.158225):0.03500000000000006,Orang:0.27664999999999997):0.05954999999999988,Mouse:1.1802124999999999,Gibbon:0.3429875000000002);";
+    for row_storage in ["btree", "sorted-array"] {
+        let mut child = Command::new("target/debug/speedytree")
+            .args(["--adaptive-switch", "--row-storage", row_storage])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("Failed to spawn child process");
+
+        let stdin = child.stdin.as_mut().unwrap();
+        stdin.write_all(input.as_bytes()).unwrap();
+
+        let mut output = String::new();
+        child
+            .stdout
+            .as_mut()
+            .unwrap()
+            .read_to_string(&mut output)
+            .unwrap();
+
+        let status = child.wait().unwrap();
+        assert!(status.success());
+
+        assert_eq!(output.trim(), expected_output);
+    }
+}
+
+#[test]
+fn naive_percentage_bounds() {
+    let input = "5
+    a	0	5	9	9	8
+    b	5	0	10	10	9
+    c	9	10	0	8	7
+    d	9	10	8	0	3
+    e	8	9	7	3	0
+";
+    let run = |args: &[&str]| {
+        let mut child = Command::new("target/debug/speedytree")
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("Failed to spawn child process");
+        // A rejected invocation may exit before reading its input
+        let _ = child.stdin.take().unwrap().write_all(input.as_bytes());
+        child.wait_with_output().unwrap()
+    };
+    let stdout = |output: std::process::Output| String::from_utf8(output.stdout).unwrap();
+    // 0 and 100 percent are the rapidnj heuristic and the naive algorithm alone
+    let rapid = run(&["--naive-percentage", "0"]);
+    assert!(rapid.status.success());
+    assert_eq!(stdout(rapid), stdout(run(&["--rapidnj"])));
+    let naive = run(&["--naive-percentage", "100"]);
+    assert!(naive.status.success());
+    assert_eq!(stdout(naive), stdout(run(&["--naive"])));
+    assert!(!run(&["--naive-percentage", "101"]).status.success());
+}
@@ -0,0 +1,59 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn run(args: &[&str], input: &str) -> Output {
+    let mut child = Command::new("target/debug/speedytree")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to spawn child process");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+// Violates the triangle inequality
+const INPUT: &str = "5
+a	0	9	1	9	5
+b	9	0	9	1	5
+c	1	9	0	1	2
+d	9	1	1	0	7
+e	5	5	2	7	0
+";
+
+#[test]
+fn negative_branches_are_kept_by_default() {
+    let output = run(&["--naive"], INPUT);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap().trim(),
+        "((e:1.25,(a:2.25,c:-1.25):1.75):4.25,b:1.5,d:-0.5);"
+    );
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn negative_branches_policies() {
+    let expected = [
+        ("zero", "((e:1.25,(a:2.25,c:0.0):1.75):4.25,b:1.5,d:0.0);"),
+        (
+            "transfer",
+            "((e:1.25,(a:1.0,c:0.0):1.75):4.25,b:1.0,d:0.0);",
+        ),
+    ];
+    for (policy, tree) in expected {
+        let output = run(&["--naive", "--negative-branches", policy], INPUT);
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8(output.stdout).unwrap().trim(), tree);
+        assert_eq!(
+            String::from_utf8(output.stderr).unwrap().trim(),
+            "Adjusted 2 negative branch lengths"
+        );
+    }
+}
@@ -0,0 +1,66 @@
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+
+const PRIMATES: &str = "6
+    Mouse     0.0000 1.5232 1.4841 1.4465 1.4389 1.4629 
+    Gibbon    1.5232 0.0000 0.7115 0.5958 0.6179 0.5583 
+    Orang     1.4841 0.7115 0.0000 0.4631 0.5061 0.4710 
+    Gorilla   1.4465 0.5958 0.4631 0.0000 0.3484 0.3083 
+    Chimp     1.4389 0.6179 0.5061 0.3484 0.0000 0.2692 
+    Human     1.4629 0.5583 0.4710 0.3083 0.2692 0.0000
+";
+
+fn run_speedytree(args: &[&str], input: &str) -> (bool, String) {
+    let mut child = Command::new("target/debug/speedytree")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("Failed to spawn child process");
+
+    let stdin = child.stdin.as_mut().unwrap();
+    stdin.write_all(input.as_bytes()).unwrap();
+
+    let mut output = String::new();
+    child
+        .stdout
+        .as_mut()
+        .unwrap()
+        .read_to_string(&mut output)
+        .unwrap();
+
+    let status = child.wait().unwrap();
+    (status.success(), output.trim().to_string())
+}
+
+#[test]
+fn simple_tree_naive_f32() {
+    // Branch lengths are written with the shortest decimals of the f32 value
+    let expected_output = "(((Gorilla:0.15822499,(Chimp:0.15009995,Human:0.11910005):0.035525024):0.03499999,Orang:0.27664998):0.059550017,Mouse:1.1802125,Gibbon:0.34298754);";
+    let (success, output) = run_speedytree(&["--naive", "--f32"], PRIMATES);
+    assert!(success);
+    assert_eq!(output, expected_output);
+}
+
+#[test]
+fn simple_tree_rapidnj_f32() {
+    let expected_output = "(((Gorilla:0.15822499,(Chimp:0.15009989,Human:0.11910011):0.035525024):0.03499999,Orang:0.27665007):0.059549928,Mouse:1.1802125,Gibbon:0.34298754);";
+    let (success, output) = run_speedytree(&["--rapidnj", "--f32"], PRIMATES);
+    assert!(success);
+    assert_eq!(output, expected_output);
+}
+
+#[test]
+fn simple_tree_hybrid_f32() {
+    let (success, output) =
+        run_speedytree(&["--hybrid", "--naive-percentage", "50", "--f32"], PRIMATES);
+    assert!(success);
+    assert!(output.contains("(Chimp:0.15009989,Human:0.11910011)"));
+}
+
+#[test]
+fn f32_is_not_available_for_bionj() {
+    let (success, _) = run_speedytree(&["--bionj", "--f32"], PRIMATES);
+    assert!(!success);
+}
@@ -0,0 +1,41 @@
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+
+fn run_unj(input: &str) -> String {
+    let mut child = Command::new("target/debug/speedytree")
+        .arg("--unj")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Failed to spawn child process");
+
+    let stdin = child.stdin.as_mut().unwrap();
+    stdin.write_all(input.as_bytes()).unwrap();
+
+    let mut output = String::new();
+    child
+        .stdout
+        .as_mut()
+        .unwrap()
+        .read_to_string(&mut output)
+        .unwrap();
+
+    let status = child.wait().unwrap();
+    assert!(status.success());
+    output
+}
+
+#[test]
+fn wikipedia_example_unj() {
+    //https://en.wikipedia.org/wiki/Neighbor_joining
+    let input = "5
+    a	0	5	9	9	8
+    b	5	0	10	10	9
+    c	9	10	0	8	7
+    d	9	10	8	0	3
+    e	8	9	7	3	0
+";
+    // Additive distances, so the tree is the same as with neighbor joining
+    let expected_output = "(((d:2.0,e:1.0):2.0,c:4.0):3.0,a:2.0,b:3.0);";
+    assert_eq!(run_unj(input).trim(), expected_output);
+}